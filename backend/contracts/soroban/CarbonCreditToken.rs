@@ -2,13 +2,14 @@
 // Compatible with OpenZeppelin Stellar Soroban Contracts ^0.4.1
 // Carbon Credit Token for Equitable Finance - Mandla Rice MRV System
 #![no_std]
+#![allow(clippy::too_many_arguments)]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, Map, String, Symbol, Vec,
-    symbol, vec, map, contracterror, panic_with_error, IntoVal, TryFromVal, Val,
+    contract, contractimpl, contracttype, symbol_short, token, xdr::ToXdr, Address, Bytes, BytesN,
+    Env, IntoVal, Map, String, Symbol, TryFromVal, Val, Vec, contracterror, panic_with_error,
 };
 use stellar_macros::default_impl;
-use stellar_tokens::fungible::{Base, FungibleToken};
+use stellar_tokens::fungible::{burnable::emit_burn, emit_transfer, Base, FungibleToken};
 
 /// Carbon Credit Token Contract for Equitable Finance
 /// This contract enables small farmers to participate in carbon markets
@@ -17,6 +18,70 @@ use stellar_tokens::fungible::{Base, FungibleToken};
 #[contract]
 pub struct CarbonCreditToken;
 
+/// Approximate number of ledgers closed per day (5 second close time)
+const DAY_IN_LEDGERS: u32 = 17280;
+
+/// Maximum number of records returned by a paginated query
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Token base units in one ton CO2e (6 decimals)
+const TON: i128 = 1_000_000;
+
+/// Basis points in 100%
+const BPS_DENOMINATOR: i128 = 10_000;
+
+/// Highest protocol fee the admin may set (10%)
+const MAX_FEE_BPS: u32 = 1_000;
+
+/// Scale of fixed-point coordinates (degrees * 1e7)
+const COORDINATE_SCALE: i128 = 10_000_000;
+
+/// Maximum number of entries accepted by a batch operation
+const MAX_BATCH_SIZE: u32 = 50;
+
+/// Maximum number of trades kept in a credit's price history
+const MAX_PRICE_HISTORY: u32 = 100;
+
+/// Maximum number of trades kept in the global recent trades feed
+const MAX_RECENT_TRADES: u32 = 100;
+
+/// Number of latest trades averaged into a credit's reference price
+const PRICE_REFERENCE_TRADES: u32 = 10;
+
+/// Fewest trades a credit needs before its price deviation is checked
+const MIN_PRICE_REFERENCE_TRADES: u32 = 3;
+
+/// Number of farmers tracked on the minting leaderboard
+const LEADERBOARD_SIZE: u32 = 20;
+
+/// Highest resale royalty a credit may carry (10%)
+const MAX_ROYALTY_BPS: u32 = 1_000;
+
+/// Maximum number of metadata entries a credit may carry
+const MAX_METADATA_KEYS: u32 = 20;
+
+/// Maximum number of flags kept for a single credit, and of unresolved flags on it
+const MAX_FLAGS: u32 = 20;
+
+/// Maximum number of members a cooperative group may have
+const MAX_GROUP_MEMBERS: u32 = 50;
+
+/// Current version of the contract state layout, bumped whenever a field is added
+/// to `ContractState`; `upgrade_state_layout` lists what each version introduced
+const SCHEMA_VERSION: u32 = 12;
+
+/// Length of a base58 CIDv0 IPFS hash
+const CIDV0_LEN: usize = 46;
+
+/// Shortest base32 CIDv1 IPFS hash accepted
+const MIN_CIDV1_LEN: usize = 50;
+
+/// Longest IPFS hash accepted
+const MAX_CID_LEN: usize = 128;
+
+/// Maximum length of generated credit and order identifiers
+const MAX_ID_LEN: usize = 256;
+
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(u32)]
@@ -33,6 +98,121 @@ pub enum CarbonCreditError {
     VerificationFailed = 5,
     /// Market not open
     MarketNotOpen = 6,
+    /// Order has been completely filled
+    OrderDepleted = 7,
+    /// Sellers cannot buy their own orders
+    SelfTradeNotAllowed = 8,
+    /// Credit is past its validity period and can no longer be traded
+    CreditExpired = 9,
+    /// Arithmetic on amounts or prices overflowed
+    ArithmeticOverflow = 10,
+    /// Contract is paused
+    ContractPaused = 11,
+    /// Credit is still within its lock period
+    CreditLocked = 12,
+    /// Trade price deviates too far from the credit's recent trades
+    PriceOutOfBounds = 13,
+    /// Recorded credit totals disagree with the token supply
+    StateInconsistent = 14,
+    /// Trade would cost more than the buyer's maximum total price
+    SlippageExceeded = 15,
+    /// Active orders of a credit would list more than the credit's amount
+    OverListed = 16,
+    /// Token movements are frozen by the admin
+    TokensFrozen = 17,
+    /// Farmer has reached the mint rate limit for the current window
+    RateLimited = 18,
+}
+
+/// Verification level of a carbon credit, ordered from lowest to highest assurance
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerificationLevel {
+    Basic,
+    Standard,
+    Premium,
+    Gold,
+}
+
+impl VerificationLevel {
+    /// Numeric rank used to compare levels (Basic = 0, Gold = 3)
+    pub fn rank(&self) -> u32 {
+        match self {
+            VerificationLevel::Basic => 0,
+            VerificationLevel::Standard => 1,
+            VerificationLevel::Premium => 2,
+            VerificationLevel::Gold => 3,
+        }
+    }
+
+    /// Parse the legacy string representation of a level
+    fn from_name(e: &Env, name: &String) -> Option<VerificationLevel> {
+        if *name == String::from_str(e, "Basic") {
+            Some(VerificationLevel::Basic)
+        } else if *name == String::from_str(e, "Standard") {
+            Some(VerificationLevel::Standard)
+        } else if *name == String::from_str(e, "Premium") {
+            Some(VerificationLevel::Premium)
+        } else if *name == String::from_str(e, "Gold") {
+            Some(VerificationLevel::Gold)
+        } else {
+            None
+        }
+    }
+}
+
+/// Standardized purpose of a retirement, for aggregating offset reporting
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetirementReason {
+    /// Voluntary offsetting of the retiree's own emissions
+    VoluntaryOffset,
+    /// Offsetting under a compliance scheme
+    ComplianceOffset,
+    /// Retired on behalf of another party
+    Donation,
+    /// Any other purpose, described in the note
+    Other,
+}
+
+/// How basis point fees and royalties round fractional base units
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RoundingMode {
+    /// Round down, leaving the remainder with the seller
+    Floor,
+    /// Round halves up, so the fee or royalty recipient gains the remainder from half a unit
+    HalfUp,
+}
+
+/// Lifecycle status of a carbon credit
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CreditStatus {
+    Pending,
+    Verified,
+    Retired,
+    Cancelled,
+    Merged,
+}
+
+impl CreditStatus {
+    /// Parse the legacy string representation of a status
+    fn from_name(e: &Env, name: &String) -> Option<CreditStatus> {
+        if *name == String::from_str(e, "Pending") {
+            Some(CreditStatus::Pending)
+        } else if *name == String::from_str(e, "Verified") {
+            Some(CreditStatus::Verified)
+        } else if *name == String::from_str(e, "Retired") {
+            Some(CreditStatus::Retired)
+        } else if *name == String::from_str(e, "Cancelled") {
+            Some(CreditStatus::Cancelled)
+        } else if *name == String::from_str(e, "Merged") {
+            Some(CreditStatus::Merged)
+        } else {
+            None
+        }
+    }
 }
 
 /// Carbon Credit metadata structure
@@ -50,7 +230,7 @@ pub struct CarbonCredit {
     /// Carbon sequestration amount in tons CO2e
     pub carbon_amount: i128,
     /// Verification level (Basic, Standard, Premium, Gold)
-    pub verification_level: String,
+    pub verification_level: VerificationLevel,
     /// Methodology used (IPCC 2019, etc.)
     pub methodology: String,
     /// Vintage year
@@ -59,20 +239,229 @@ pub struct CarbonCredit {
     pub report_hash: String,
     /// Timestamp of credit creation
     pub created_at: u64,
-    /// Credit status (Pending, Verified, Retired, Cancelled)
-    pub status: String,
-    /// Geographic coordinates (latitude, longitude)
+    /// Credit status (Pending, Verified, Retired, Cancelled, Merged)
+    pub status: CreditStatus,
+    /// Geographic coordinates (latitude, longitude) in degrees scaled by 1e7
+    pub coordinates: Vec<i128>,
+    /// Additional metadata
+    pub metadata: Map<String, String>,
+    /// Farmer the credit was originally issued to
+    pub original_farmer: Address,
+    /// Royalty paid to the original farmer on resales, in basis points
+    pub royalty_bps: u32,
+}
+
+/// Storage keys used by the contract
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataKey {
+    /// Contract state (instance storage)
+    State,
+    /// Admin proposed by the current admin, awaiting acceptance (instance storage)
+    PendingAdmin,
+    /// Carbon credit record by id
+    Credit(String),
+    /// Market order by id
+    Order(String),
+    /// Number of ids in an index
+    IndexLen(Index),
+    /// Id at a position of an index
+    IndexEntry(Index, u32),
+    /// Position of an id in an index, so it can be removed without a scan
+    IndexPosition(Index, String),
+    /// Total (minted, retired) tons of a vintage year
+    VintageStats(u32),
+    /// Total (tons traded, payment value) of a vintage year's trades in the payment token
+    VintageTrades(u32),
+    /// Lifetime payments received by a seller from credit sales and royalties
+    FarmerEarnings(Address),
+    /// Total tons minted in a one-degree grid cell
+    RegionTotal(String),
+    /// Keys of every grid cell credits have been minted in
+    Regions,
+    /// Marks a farmer that has been issued at least one credit
+    Farmer(Address),
+    /// Lifetime tons minted to a farmer
+    FarmerMinted(Address),
+    /// Times of a farmer's mints within the current rate limit window, oldest first
+    FarmerMints(Address),
+    /// Member addresses allowed to act for a cooperative group's credits
+    GroupMembers(Address),
+    /// Farmers with the most lifetime tons minted, highest first
+    TopFarmers,
+    /// Marks an address authorized to verify credits
+    Verifier(Address),
+    /// Marks a buyer that has passed KYC
+    Allowlisted(Address),
+    /// Marks a buyer holding a role that sellers may restrict orders to
+    BuyerRole(Address, Symbol),
+    /// Role a buyer must hold to fill an order
+    BuyerRestriction(String),
+    /// Marks a token contract approved for settling orders
+    PriceAsset(Address),
+    /// Asset an order settles in, when not the default payment token
+    OrderAsset(String),
+    /// Listing fee still held for an order, the address that paid it and the
+    /// amount of the order the held fee covers
+    ListingFee(String),
+    /// Marks an address frozen for suspected fraud
+    Blocked(Address),
+    /// Marks a credit frozen while under investigation
+    FrozenCredit(String),
+    /// Time before which a credit minted with a lock may not be listed or transferred
+    CreditUnlockAt(String),
+    /// Number of provenance entries recorded for a credit
+    ProvenanceCount(String),
+    /// Provenance entry of a credit by position, oldest first
+    Provenance(String, u32),
+    /// Owners of a credit with the time each became owner, oldest first
+    CreditOwners(String),
+    /// Flags raised against a credit
+    CreditFlags(String),
+    /// Number of unresolved flags on a credit, the newest entries of its flags
+    FlaggedCredit(String),
+    /// Credit minted from an MRV report, by report hash
+    ReportHash(String),
+    /// Time a credit was last re-verified; its validity runs from then rather than its vintage
+    VerifiedAt(String),
+    /// Credit minted for a farm's season, by farm id and season id
+    FarmSeason(String, String),
+    /// Marks a farm season the admin allowed to be minted again as an amendment
+    AmendmentAllowed(String, String),
+    /// Marks a methodology approved for minting
+    Methodology(String),
+    /// Marks a verification level no longer accepted for new credits
+    LevelDisabled(VerificationLevel),
+    /// Tons of live verified credits at a verification level
+    LevelAmount(VerificationLevel),
+    /// Executed fills of a market order
+    OrderFill(String),
+    /// Amount of a credit escrowed in its active orders
+    ReservedAmount(String),
+    /// Executed trades of a credit, oldest first
+    PriceHistory(String),
+    /// Latest trades across all credits, oldest first
+    RecentTrades,
+    /// Dutch auction parameters of an order
+    Auction(String),
+    /// Number of escrowed purchases created (instance storage)
+    EscrowCount,
+    /// Escrowed purchase by id
+    Escrow(String),
+    /// Retirement certificate by id
+    Certificate(String),
+    /// Ids of the retirement certificates issued against a credit
+    CreditRetirements(String),
+    /// Number of batch retirements performed (instance storage)
+    BatchRetirementCount,
+    /// Aggregate certificate of a batch retirement by id
+    AggregateCertificate(String),
+}
+
+/// Lists of ids stored one entry per key, so they are never rewritten as they grow
+/// An id leaving a list is replaced by the list's last id, so only lists ids never
+/// leave keep their creation order
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Index {
+    /// Ids of every credit record ever created, oldest first
+    AllCredits,
+    /// Ids of the credits held by a farmer
+    FarmerCredits(Address),
+    /// Ids of the live credits of a vintage year
+    VintageCredits(u32),
+    /// Ids of the live credits of a methodology
+    MethodologyCredits(String),
+    /// Ids of the credits awaiting verification
+    PendingCredits,
+    /// Ids of every market order ever created, oldest first
+    AllOrders,
+    /// Ids of all active market orders
+    ActiveOrders,
+    /// Ids of the active market orders for a credit
+    CreditOrders(String),
+    /// Ids of a seller's active orders
+    SellerOrders(Address),
+    /// Ids of the retirement certificates issued for a purpose, oldest first
+    ReasonRetirements(RetirementReason),
+    /// Ids of the retirement certificates issued to a retiree, oldest first
+    RetireeCertificates(Address),
+}
+
+/// Parameters of a single mint, used for batch minting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MintRequest {
+    /// Farmer receiving the credit
+    pub farmer_address: Address,
+    /// Farm identifier
+    pub farm_id: String,
+    /// Season identifier
+    pub season_id: String,
+    /// Carbon sequestration amount in tons CO2e
+    pub carbon_amount: i128,
+    /// Verification level of the MRV report
+    pub verification_level: VerificationLevel,
+    /// Methodology used (IPCC 2019, etc.)
+    pub methodology: String,
+    /// Vintage year
+    pub vintage: u32,
+    /// IPFS hash of MRV report
+    pub report_hash: String,
+    /// Geographic coordinates (latitude, longitude) in degrees scaled by 1e7
     pub coordinates: Vec<i128>,
     /// Additional metadata
     pub metadata: Map<String, String>,
 }
 
+/// Carbon credit layout used before verification levels became typed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LegacyCarbonCredit {
+    pub id: String,
+    pub farmer_address: Address,
+    pub farm_id: String,
+    pub season_id: String,
+    pub carbon_amount: i128,
+    pub verification_level: String,
+    pub methodology: String,
+    pub vintage: u32,
+    pub report_hash: String,
+    pub created_at: u64,
+    pub status: String,
+    pub coordinates: Vec<i128>,
+    pub metadata: Map<String, String>,
+}
+
+/// Carbon credit layout used before statuses became typed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StringStatusCarbonCredit {
+    pub id: String,
+    pub farmer_address: Address,
+    pub farm_id: String,
+    pub season_id: String,
+    pub carbon_amount: i128,
+    pub verification_level: VerificationLevel,
+    pub methodology: String,
+    pub vintage: u32,
+    pub report_hash: String,
+    pub created_at: u64,
+    pub status: String,
+    pub coordinates: Vec<i128>,
+    pub metadata: Map<String, String>,
+}
+
 /// Market order structure for carbon credit trading
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct MarketOrder {
     /// Order identifier
     pub id: String,
+    /// Carbon credit being sold
+    pub credit_id: String,
+    /// Seller receiving payment for the order
+    pub seller_address: Address,
     /// Order type (Buy/Sell)
     pub order_type: String,
     /// Carbon credit amount
@@ -85,6 +474,217 @@ pub struct MarketOrder {
     pub timestamp: u64,
 }
 
+/// Executed fills of a market order, kept after it is filled or cancelled
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OrderFill {
+    /// Total amount bought from the order
+    pub filled_amount: i128,
+    /// Price per ton paid in the latest fill
+    pub last_price_per_ton: i128,
+    /// Timestamp of the latest fill
+    pub last_filled_at: u64,
+}
+
+/// Cost breakdown of a prospective purchase
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BuyQuote {
+    /// Cost of the amount at the current price per ton
+    pub base_price: i128,
+    /// Protocol fee taken from the seller's share
+    pub fee: i128,
+    /// Resale royalty paid to the original farmer from the seller's share
+    pub royalty: i128,
+    /// Amount charged to the buyer
+    pub total: i128,
+}
+
+/// Dutch auction parameters for an auction order
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Auction {
+    /// Price per ton when the auction opens
+    pub start_price: i128,
+    /// Lowest price per ton the auction decays to
+    pub floor_price: i128,
+    /// Timestamp the auction opened
+    pub start_time: u64,
+    /// Seconds taken to decay from the start price to the floor price
+    pub duration_secs: u64,
+}
+
+impl Auction {
+    /// Linearly decayed price per ton at `timestamp`, clamped to the floor
+    pub fn price_at(&self, e: &Env, timestamp: u64) -> i128 {
+        let elapsed = timestamp.saturating_sub(self.start_time);
+        if elapsed >= self.duration_secs {
+            return self.floor_price;
+        }
+        let spread = self.start_price - self.floor_price;
+        let decay = checked_mul(e, spread, elapsed as i128) / self.duration_secs as i128;
+        self.start_price - decay
+    }
+}
+
+/// Purchase whose payment is held by the contract until it is confirmed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Escrow {
+    /// Escrow identifier
+    pub id: String,
+    /// Order the credits were reserved from
+    pub order_id: String,
+    /// Buyer whose payment is held
+    pub buyer: Address,
+    /// Carbon credit amount reserved
+    pub amount: i128,
+    /// Price per ton locked in when the escrow was created
+    pub price_per_ton: i128,
+    /// Escrow status (Pending, Settled, Refunded)
+    pub status: String,
+    /// Escrow timestamp
+    pub created_at: u64,
+}
+
+/// High-level impact totals for UN reporting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImpactSummary {
+    /// Total carbon credits minted
+    pub total_minted: i128,
+    /// Total carbon credits retired
+    pub total_retired: i128,
+    /// Credits neither retired nor escrowed in an active order
+    pub total_active: i128,
+    /// Number of participating farmers
+    pub farmer_count: u32,
+    /// Total market volume
+    pub total_market_volume: i128,
+    /// Average price per ton across all trades (0 before the first trade)
+    pub average_price: i128,
+}
+
+/// Holdings of a credit owner, valued at each credit's latest trade price
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Portfolio {
+    /// Tons across all credits held
+    pub total_tons: i128,
+    /// Tons held per vintage year
+    pub tons_by_vintage: Map<u32, i128>,
+    /// Value of the tons at each credit's latest trade price; credits never traded count as 0
+    pub market_value: i128,
+}
+
+/// Executed trade recorded for price transparency
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TradePoint {
+    /// Price per ton CO2e paid
+    pub price_per_ton: i128,
+    /// Amount traded
+    pub amount: i128,
+    /// Timestamp of the trade
+    pub timestamp: u64,
+}
+
+/// Trade in the global activity feed
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TradeRecord {
+    /// Order the trade filled
+    pub order_id: String,
+    /// Carbon credit traded
+    pub credit_id: String,
+    /// Seller address
+    pub seller: Address,
+    /// Buyer address
+    pub buyer: Address,
+    /// Price per ton CO2e paid
+    pub price_per_ton: i128,
+    /// Amount traded
+    pub amount: i128,
+    /// Timestamp of the trade
+    pub timestamp: u64,
+}
+
+/// Step in a credit's lifecycle, recorded for auditors
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ProvenanceEntry {
+    /// What happened, e.g. `mint`, `list`, `trade` or `retire`
+    pub action: Symbol,
+    /// Address that performed or received the action
+    pub actor: Address,
+    /// Amount in tons CO2e the action covered
+    pub amount: i128,
+    /// Timestamp of the action
+    pub timestamp: u64,
+}
+
+/// Report that a credit looks suspicious, raised by a community member or auditor
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Flag {
+    /// Address that raised the flag
+    pub flagger: Address,
+    /// Why the credit is suspected
+    pub reason: String,
+    /// Timestamp of the flag
+    pub timestamp: u64,
+}
+
+/// Permanent proof that credits were retired, for compliance reporting
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetirementCertificate {
+    /// Certificate identifier
+    pub id: String,
+    /// Carbon credit the retired amount was drawn from
+    pub credit_id: String,
+    /// Address whose tokens were burned
+    pub retiree: Address,
+    /// Retired amount in tons CO2e
+    pub amount: i128,
+    /// Purpose of the retirement
+    pub reason: RetirementReason,
+    /// Free-text detail on the retirement
+    pub note: Option<String>,
+    /// Timestamp of the retirement
+    pub retired_at: u64,
+}
+
+/// Amount to retire from one credit in a batch retirement
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RetireRequest {
+    /// Carbon credit to retire from
+    pub credit_id: String,
+    /// Amount to retire in tons CO2e
+    pub amount: i128,
+}
+
+/// Certificate covering every retirement of a batch
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AggregateCertificate {
+    /// Certificate identifier
+    pub id: String,
+    /// Address whose tokens were burned
+    pub retiree: Address,
+    /// Per-credit certificates issued by the batch
+    pub certificate_ids: Vec<String>,
+    /// Total retired amount in tons CO2e
+    pub total_amount: i128,
+    /// Purpose of the retirement
+    pub reason: RetirementReason,
+    /// Free-text detail on the retirement
+    pub note: Option<String>,
+    /// Timestamp of the retirement
+    pub retired_at: u64,
+}
+
 /// Contract state structure
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -93,22 +693,132 @@ pub struct ContractState {
     pub total_credits_minted: i128,
     /// Total carbon credits retired
     pub total_credits_retired: i128,
+    /// Total credit tokens burned
+    pub total_tokens_burned: i128,
     /// Total market volume
     pub total_market_volume: i128,
+    /// Total credits bought on the market in the payment token, matching the volume
+    pub total_credits_traded: i128,
+    /// Credits currently escrowed in active orders
+    pub total_credits_listed: i128,
     /// Number of participating farmers
     pub farmer_count: u32,
     /// Contract admin address
     pub admin: Address,
+    /// Token contract used to settle trades (XLM Stellar Asset Contract)
+    pub payment_token: Address,
+    /// Market open status
+    pub market_open: bool,
+    /// Minimum verification level for trading
+    pub min_verification_level: VerificationLevel,
+    /// Remaining TTL (in ledgers) below which persistent records are extended
+    pub ttl_threshold: u32,
+    /// TTL (in ledgers) persistent records are extended to
+    pub ttl_extend_to: u32,
+    /// Oldest vintage year accepted for new credits
+    pub min_vintage: u32,
+    /// Protocol fee on trades in basis points
+    pub fee_bps: u32,
+    /// Address receiving protocol fees charged in assets other than the payment token
+    pub treasury: Address,
+    /// Years after its vintage a credit remains tradable (0 = never expires)
+    pub validity_years: u32,
+    /// Whether buyers must be on the KYC allowlist
+    pub require_buyer_kyc: bool,
+    /// Resale royalty in basis points stamped on newly minted credits
+    pub royalty_bps: u32,
+    /// Whether all credit and market operations are halted
+    pub contract_paused: bool,
+    /// Version of the deployed contract code, bumped on every upgrade
+    pub version: u32,
+    /// Version of the storage layout, advanced by `migrate`
+    pub schema_version: u32,
+    /// Smallest carbon amount accepted for a single credit
+    pub min_mint_amount: i128,
+    /// Largest carbon amount accepted for a single credit
+    pub max_mint_amount: i128,
+    /// Base units that minted and retired amounts must be a multiple of
+    pub amount_unit: i128,
+    /// Furthest a trade may stray from the credit's recent average price; 0 disables the check
+    pub max_price_deviation_bps: u32,
+    /// Ed25519 key of the external verification oracle; when set every mint must carry
+    /// the oracle's signature
+    pub verifier_pubkey: Option<BytesN<32>>,
+    /// Protocol fees in the payment token held by the contract until withdrawn
+    pub accumulated_fees: i128,
+    /// Rounding of fee and royalty amounts; the seller absorbs the remainder
+    pub rounding_mode: RoundingMode,
+    /// Emergency freeze of every token movement, including direct transfers
+    pub tokens_frozen: bool,
+    /// Number of credits minted so far, suffixed to new credit ids to keep them unique
+    pub credit_nonce: u64,
+    /// Anti-spam fee in the payment token held per new order, refunded in proportion
+    /// to the amount sold and forfeited to the treasury for the amount cancelled; 0 disables it
+    pub list_fee: i128,
+    /// Most credits a farmer may be minted within `mint_rate_window` seconds; 0 disables the limit
+    pub mint_rate_limit: u32,
+    /// Length in seconds of the rolling window the mint rate limit applies to
+    pub mint_rate_window: u64,
+}
+
+/// Admin-tunable parameters of the contract, read in one call by clients
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ContractConfig {
+    /// Contract admin address
+    pub admin: Address,
+    /// Address receiving protocol fees charged in assets other than the payment token
+    pub treasury: Address,
+    /// Token contract used to settle trades
+    pub payment_token: Address,
     /// Market open status
     pub market_open: bool,
     /// Minimum verification level for trading
-    pub min_verification_level: String,
+    pub min_verification_level: VerificationLevel,
+    /// Whether all credit and market operations are halted
+    pub contract_paused: bool,
+    /// Emergency freeze of every token movement
+    pub tokens_frozen: bool,
+    /// Whether buyers must be on the KYC allowlist
+    pub require_buyer_kyc: bool,
+    /// Protocol fee on trades in basis points
+    pub fee_bps: u32,
+    /// Resale royalty in basis points stamped on newly minted credits
+    pub royalty_bps: u32,
+    /// Rounding of fee and royalty amounts
+    pub rounding_mode: RoundingMode,
+    /// Anti-spam fee held per new order; 0 disables it
+    pub list_fee: i128,
+    /// Most credits a farmer may be minted per rolling window; 0 disables the limit
+    pub mint_rate_limit: u32,
+    /// Length in seconds of the mint rate limit window
+    pub mint_rate_window: u64,
+    /// Furthest a trade may stray from the credit's recent average price; 0 disables the check
+    pub max_price_deviation_bps: u32,
+    /// Oldest vintage year accepted for new credits
+    pub min_vintage: u32,
+    /// Years after its vintage a credit remains tradable (0 = never expires)
+    pub validity_years: u32,
+    /// Smallest carbon amount accepted for a single credit
+    pub min_mint_amount: i128,
+    /// Largest carbon amount accepted for a single credit
+    pub max_mint_amount: i128,
+    /// Base units that minted and retired amounts must be a multiple of
+    pub amount_unit: i128,
+    /// Ed25519 key of the external verification oracle, if mints must be attested
+    pub verifier_pubkey: Option<BytesN<32>>,
+    /// Remaining TTL (in ledgers) below which persistent records are extended
+    pub ttl_threshold: u32,
+    /// TTL (in ledgers) persistent records are extended to
+    pub ttl_extend_to: u32,
 }
 
 #[contractimpl]
 impl CarbonCreditToken {
     /// Initialize the carbon credit token contract
-    pub fn __constructor(e: &Env) {
+    /// `admin` is the MRV system account allowed to mint and manage the market
+    /// `payment_token` is the token buyers pay sellers with
+    pub fn __constructor(e: &Env, admin: Address, payment_token: Address) {
         // Set token metadata
         Base::set_metadata(
             e, 
@@ -118,18 +828,52 @@ impl CarbonCreditToken {
         );
         
         // Initialize contract state
-        let admin = e.current_contract_address();
+        let treasury = admin.clone();
         let state = ContractState {
             total_credits_minted: 0,
             total_credits_retired: 0,
+            total_tokens_burned: 0,
             total_market_volume: 0,
+            total_credits_traded: 0,
+            total_credits_listed: 0,
             farmer_count: 0,
             admin,
+            payment_token,
             market_open: true,
-            min_verification_level: String::from_str(e, "Basic"),
+            min_verification_level: VerificationLevel::Basic,
+            ttl_threshold: 30 * DAY_IN_LEDGERS,
+            ttl_extend_to: 180 * DAY_IN_LEDGERS,
+            min_vintage: 2000,
+            fee_bps: 0,
+            treasury,
+            validity_years: 0,
+            require_buyer_kyc: false,
+            royalty_bps: 0,
+            contract_paused: false,
+            version: 1,
+            schema_version: SCHEMA_VERSION,
+            min_mint_amount: TON,
+            max_mint_amount: 10_000 * TON,
+            amount_unit: 1,
+            max_price_deviation_bps: 0,
+            verifier_pubkey: None,
+            accumulated_fees: 0,
+            rounding_mode: RoundingMode::Floor,
+            tokens_frozen: false,
+            credit_nonce: 0,
+            list_fee: 0,
+            mint_rate_limit: 0,
+            mint_rate_window: 0,
         };
         
-        e.storage().instance().set(&state);
+        e.storage().instance().set(&DataKey::State, &state);
+
+        // Approve the methodologies used by the MRV system out of the box
+        for methodology in ["IPCC 2019", "CDM AMS-III.AU"] {
+            let key = DataKey::Methodology(String::from_str(e, methodology));
+            e.storage().persistent().set(&key, &true);
+            extend_ttl(e, &state, &key);
+        }
     }
 
     /// Mint new carbon credits for a farmer
@@ -140,7 +884,7 @@ impl CarbonCreditToken {
         farm_id: String,
         season_id: String,
         carbon_amount: i128,
-        verification_level: String,
+        verification_level: VerificationLevel,
         methodology: String,
         vintage: u32,
         report_hash: String,
@@ -148,238 +892,3771 @@ impl CarbonCreditToken {
         metadata: Map<String, String>,
     ) -> Result<String, CarbonCreditError> {
         // Verify caller is authorized (MRV system or admin)
-        let caller = e.current_contract_address();
-        let state: ContractState = e.storage().instance().get().unwrap();
-        
-        if caller != state.admin {
-            panic_with_error!(e, CarbonCreditError::NotAuthorized);
-        }
-
-        // Validate carbon credit data
-        if carbon_amount <= 0 {
-            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
-        }
-
-        // Generate unique credit ID
-        let credit_id = format!("{}_{}_{}_{}", farm_id, season_id, vintage, e.ledger().timestamp());
-        let credit_id_string = String::from_str(e, &credit_id);
-
-        // Check if credit already exists
-        if e.storage().instance().has(&credit_id_string) {
-            panic_with_error!(e, CarbonCreditError::CreditAlreadyExists);
-        }
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
 
-        // Create carbon credit
-        let credit = CarbonCredit {
-            id: credit_id_string.clone(),
-            farmer_address: farmer_address.clone(),
-            farm_id,
-            season_id,
-            carbon_amount,
-            verification_level,
-            methodology,
-            vintage,
-            report_hash,
-            created_at: e.ledger().timestamp(),
-            status: String::from_str(e, "Verified"),
-            coordinates,
-            metadata,
-        };
-
-        // Store the credit
-        e.storage().instance().set(&credit_id_string, &credit);
-
-        // Update contract state
-        let mut new_state = state;
-        new_state.total_credits_minted += carbon_amount;
-        new_state.farmer_count += 1;
-        e.storage().instance().set(&new_state);
-
-        // Mint tokens to farmer (1 token = 1 ton CO2e)
-        Base::mint(e, &farmer_address, &carbon_amount);
-
-        Ok(credit_id)
+        Ok(mint_credit(
+            e,
+            MintRequest {
+                farmer_address,
+                farm_id,
+                season_id,
+                carbon_amount,
+                verification_level,
+                methodology,
+                vintage,
+                report_hash,
+                coordinates,
+                metadata,
+            },
+            None,
+        ))
     }
 
-    /// Get carbon credit details
-    pub fn get_carbon_credit(e: &Env, credit_id: String) -> Option<CarbonCredit> {
-        e.storage().instance().get(&credit_id)
+    /// Mint a carbon credit attested by the external verification oracle (admin only)
+    /// `signature` is the oracle's ed25519 signature over the SHA-256 hash of the
+    /// request's XDR encoding
+    pub fn mint_attested_carbon_credit(
+        e: &Env,
+        request: MintRequest,
+        signature: BytesN<64>,
+    ) -> Result<String, CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        Ok(mint_credit(e, request, Some(signature)))
     }
 
-    /// List carbon credit for sale on marketplace
-    /// Enables farmers to monetize their environmental contributions
-    pub fn list_for_sale(
+    /// Mint a carbon credit that cannot be listed or transferred before `unlock_at`,
+    /// discouraging immediate flipping; it may still be retired (admin only)
+    /// `signature` is the oracle's attestation, required once a verifier key is set
+    pub fn mint_locked_carbon_credit(
         e: &Env,
-        credit_id: String,
-        price_per_ton: i128,
+        request: MintRequest,
+        unlock_at: u64,
+        signature: Option<BytesN<64>>,
     ) -> Result<String, CarbonCreditError> {
-        let caller = e.current_contract_address();
-        let credit: CarbonCredit = e.storage().instance().get(&credit_id)
-            .ok_or(CarbonCreditError::InvalidCreditData)?;
-
-        // Verify caller owns the credit
-        if caller != credit.farmer_address {
-            panic_with_error!(e, CarbonCreditError::NotAuthorized);
-        }
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
 
-        // Create market order
-        let order_id = format!("ORDER_{}_{}", credit_id, e.ledger().timestamp());
-        let order = MarketOrder {
-            id: String::from_str(e, &order_id),
-            order_type: String::from_str(e, "Sell"),
-            amount: credit.carbon_amount,
-            price_per_ton,
-            status: String::from_str(e, "Active"),
-            timestamp: e.ledger().timestamp(),
-        };
+        let credit_id = mint_credit(e, request, signature);
+        set_unlock_at(e, &state, &credit_id, unlock_at);
 
-        // Store order
-        e.storage().instance().set(&String::from_str(e, &order_id), &order);
+        Ok(credit_id)
+    }
 
-        Ok(order_id)
+    /// Time before which a credit may not be listed or transferred; 0 if never locked
+    pub fn get_unlock_at(e: &Env, credit_id: String) -> u64 {
+        unlock_at(e, &credit_id)
     }
 
-    /// Buy carbon credits from marketplace
-    /// Enables investors to support sustainable farming
-    pub fn buy_carbon_credits(
+    /// Mint a batch of carbon credits, e.g. when onboarding a whole cooperative
+    /// The batch is atomic: if any entry is invalid no credit is minted
+    /// Once a verifier key is set, `signatures` holds the oracle's attestation of each
+    /// request in order; otherwise it may be empty
+    pub fn batch_mint_carbon_credits(
         e: &Env,
-        order_id: String,
-        buyer_address: Address,
-        amount: i128,
-    ) -> Result<(), CarbonCreditError> {
-        let state: ContractState = e.storage().instance().get().unwrap();
-        
-        if !state.market_open {
-            panic_with_error!(e, CarbonCreditError::MarketNotOpen);
-        }
-
-        let order: MarketOrder = e.storage().instance().get(&order_id)
-            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        requests: Vec<MintRequest>,
+        signatures: Vec<BytesN<64>>,
+    ) -> Result<Vec<String>, CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
 
-        if order.status != String::from_str(e, "Active") {
+        if requests.is_empty() || requests.len() > MAX_BATCH_SIZE {
             panic_with_error!(e, CarbonCreditError::InvalidCreditData);
         }
 
-        if amount > order.amount {
-            panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+        let mut credit_ids = Vec::new(e);
+        for (i, request) in requests.iter().enumerate() {
+            credit_ids.push_back(mint_credit(e, request, signatures.get(i as u32)));
         }
 
-        // Calculate total price
-        let total_price = amount * order.price_per_ton;
+        Ok(credit_ids)
+    }
 
-        // Transfer tokens from buyer to seller
-        Base::transfer(e, &buyer_address, &order.seller_address, &amount);
+    /// Authorize an address to verify credits (admin only)
+    pub fn add_verifier(e: &Env, verifier: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
 
-        // Update market volume
-        let mut new_state = state;
-        new_state.total_market_volume += total_price;
-        e.storage().instance().set(&new_state);
+        let key = DataKey::Verifier(verifier);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
 
         Ok(())
     }
 
-    /// Retire carbon credits (permanent removal from circulation)
-    /// Used when credits are used for offsetting emissions
-    pub fn retire_credits(
+    /// Revoke an address's permission to verify credits (admin only)
+    pub fn remove_verifier(e: &Env, verifier: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        e.storage().persistent().remove(&DataKey::Verifier(verifier));
+
+        Ok(())
+    }
+
+    /// Check whether an address may verify credits
+    pub fn is_verifier(e: &Env, address: Address) -> bool {
+        e.storage().persistent().has(&DataKey::Verifier(address))
+    }
+
+    /// Add a buyer that has passed KYC to the allowlist (admin or verifiers)
+    pub fn add_to_allowlist(e: &Env, manager: Address, buyer: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        let key = DataKey::Allowlisted(buyer);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        Ok(())
+    }
+
+    /// Remove a buyer from the KYC allowlist (admin or verifiers)
+    pub fn remove_from_allowlist(e: &Env, manager: Address, buyer: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        e.storage().persistent().remove(&DataKey::Allowlisted(buyer));
+
+        Ok(())
+    }
+
+    /// Check whether a buyer is on the KYC allowlist
+    pub fn is_allowlisted(e: &Env, buyer: Address) -> bool {
+        e.storage().persistent().has(&DataKey::Allowlisted(buyer))
+    }
+
+    /// Grant a buyer a role, such as `accredit`, that sellers may restrict orders to
+    /// (admin or verifiers)
+    pub fn grant_buyer_role(
+        e: &Env,
+        manager: Address,
+        buyer: Address,
+        role: Symbol,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        let key = DataKey::BuyerRole(buyer, role);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        Ok(())
+    }
+
+    /// Revoke a role from a buyer (admin or verifiers)
+    pub fn revoke_buyer_role(
+        e: &Env,
+        manager: Address,
+        buyer: Address,
+        role: Symbol,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        e.storage().persistent().remove(&DataKey::BuyerRole(buyer, role));
+
+        Ok(())
+    }
+
+    /// Check whether a buyer holds a role
+    pub fn has_buyer_role(e: &Env, buyer: Address, role: Symbol) -> bool {
+        e.storage().persistent().has(&DataKey::BuyerRole(buyer, role))
+    }
+
+    /// Let a member act for a cooperative's credits (group auth)
+    /// The group address owns the credits; a member's signature satisfies every owner
+    /// check on them, passed as the `member` argument of listing, order and credit calls
+    pub fn add_member(e: &Env, group: Address, member: Address) -> Result<(), CarbonCreditError> {
+        group.require_auth();
+
+        let key = DataKey::GroupMembers(group.clone());
+        let mut members: Vec<Address> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+        if member == group || members.contains(&member) || members.len() >= MAX_GROUP_MEMBERS {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        members.push_back(member.clone());
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        e.storage().persistent().set(&key, &members);
+        extend_ttl(e, &state, &key);
+
+        e.events().publish((symbol_short!("group"), symbol_short!("add")), (group, member));
+
+        Ok(())
+    }
+
+    /// Stop a member from acting for a cooperative's credits (group auth)
+    pub fn remove_member(e: &Env, group: Address, member: Address) -> Result<(), CarbonCreditError> {
+        group.require_auth();
+
+        let key = DataKey::GroupMembers(group.clone());
+        let mut members: Vec<Address> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+        let i = members.first_index_of(&member)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        members.remove(i);
+        e.storage().persistent().set(&key, &members);
+
+        e.events().publish((symbol_short!("group"), symbol_short!("remove")), (group, member));
+
+        Ok(())
+    }
+
+    /// Members of a cooperative group
+    pub fn get_group_members(e: &Env, group: Address) -> Vec<Address> {
+        e.storage().persistent().get(&DataKey::GroupMembers(group)).unwrap_or(Vec::new(e))
+    }
+
+    /// Freeze an address suspected of fraud (admin only)
+    pub fn block_address(e: &Env, address: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let key = DataKey::Blocked(address.clone());
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        e.events().publish((symbol_short!("admin"), symbol_short!("block")), address);
+
+        Ok(())
+    }
+
+    /// Lift the freeze on an address (admin only)
+    pub fn unblock_address(e: &Env, address: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        e.storage().persistent().remove(&DataKey::Blocked(address.clone()));
+
+        e.events().publish((symbol_short!("admin"), symbol_short!("unblock")), address);
+
+        Ok(())
+    }
+
+    /// Check whether an address is frozen
+    pub fn is_blocked(e: &Env, address: Address) -> bool {
+        e.storage().persistent().has(&DataKey::Blocked(address))
+    }
+
+    /// Verify a pending credit at the given level (verifiers only)
+    pub fn verify_credit(
         e: &Env,
+        verifier: Address,
         credit_id: String,
-        amount: i128,
-        retirement_reason: String,
+        level: VerificationLevel,
     ) -> Result<(), CarbonCreditError> {
-        let caller = e.current_contract_address();
-        let mut credit: CarbonCredit = e.storage().instance().get(&credit_id)
+        require_not_paused(e);
+
+        verifier.require_auth();
+        if !e.storage().persistent().has(&DataKey::Verifier(verifier.clone())) {
+            panic_with_error!(e, CarbonCreditError::NotAuthorized);
+        }
+
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
             .ok_or(CarbonCreditError::InvalidCreditData)?;
 
-        // Verify caller owns the credit
-        if caller != credit.farmer_address {
+        if credit.status != CreditStatus::Pending {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        require_level_accepted(e, level);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        mark_verified(e, &state, &verifier, &mut credit, level);
+
+        Ok(())
+    }
+
+    /// Verify a batch of pending credits at the given level (verifiers only)
+    /// In `strict` mode any credit that is missing or not pending fails the whole batch,
+    /// otherwise such credits are skipped; returns the ids that were verified
+    pub fn bulk_verify_credits(
+        e: &Env,
+        verifier: Address,
+        ids: Vec<String>,
+        level: VerificationLevel,
+        strict: bool,
+    ) -> Result<Vec<String>, CarbonCreditError> {
+        require_not_paused(e);
+
+        verifier.require_auth();
+        if !e.storage().persistent().has(&DataKey::Verifier(verifier.clone())) {
             panic_with_error!(e, CarbonCreditError::NotAuthorized);
         }
 
-        if amount > credit.carbon_amount {
-            panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+        if ids.is_empty() || ids.len() > MAX_BATCH_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
         }
+        require_level_accepted(e, level);
 
-        // Update credit amount
-        credit.carbon_amount -= amount;
-        if credit.carbon_amount == 0 {
-            credit.status = String::from_str(e, "Retired");
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let mut verified = Vec::new(e);
+        for credit_id in ids.iter() {
+            let credit: Option<CarbonCredit> = e.storage().persistent().get(&DataKey::Credit(credit_id.clone()));
+            match credit {
+                Some(mut credit) if credit.status == CreditStatus::Pending => {
+                    mark_verified(e, &state, &verifier, &mut credit, level);
+                    verified.push_back(credit_id);
+                }
+                _ if strict => panic_with_error!(e, CarbonCreditError::InvalidCreditData),
+                _ => {}
+            }
+        }
+
+        Ok(verified)
+    }
+
+    /// Re-verify a credit against a new MRV report (verifiers only)
+    /// The credit stays valid for `validity_years` from the year of re-verification,
+    /// so an expired credit becomes tradable again
+    pub fn reverify_credit(
+        e: &Env,
+        verifier: Address,
+        credit_id: String,
+        new_report_hash: String,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        verifier.require_auth();
+        if !e.storage().persistent().has(&DataKey::Verifier(verifier.clone())) {
+            panic_with_error!(e, CarbonCreditError::NotAuthorized);
         }
 
-        // Store updated credit
-        e.storage().instance().set(&credit_id, &credit);
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        if credit.status != CreditStatus::Verified {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        require_not_flagged(e, &credit_id);
+
+        validate_report_hash(e, &new_report_hash);
+        let report_key = DataKey::ReportHash(new_report_hash.clone());
+        if e.storage().persistent().has(&report_key) {
+            panic_with_error!(e, CarbonCreditError::CreditAlreadyExists);
+        }
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        e.storage().persistent().set(&report_key, &credit_id);
+        extend_ttl(e, &state, &report_key);
 
-        // Update contract state
-        let mut state: ContractState = e.storage().instance().get().unwrap();
-        state.total_credits_retired += amount;
-        e.storage().instance().set(&state);
+        credit.report_hash = new_report_hash.clone();
+        e.storage().persistent().set(&credit_key, &credit);
+        extend_ttl(e, &state, &credit_key);
 
-        // Burn tokens
-        Base::burn(e, &caller, &amount);
+        let verified_key = DataKey::VerifiedAt(credit_id.clone());
+        e.storage().persistent().set(&verified_key, &e.ledger().timestamp());
+        extend_ttl(e, &state, &verified_key);
+        record_provenance(e, &state, &credit_id, symbol_short!("reverify"), &verifier, credit.carbon_amount);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("reverify")),
+            (credit_id, verifier, new_report_hash),
+        );
 
         Ok(())
     }
 
-    /// Get contract statistics for transparency
-    pub fn get_contract_stats(e: &Env) -> ContractState {
-        e.storage().instance().get().unwrap()
+    /// Approve a methodology for minting (admin only)
+    pub fn approve_methodology(e: &Env, methodology: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let key = DataKey::Methodology(methodology);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        Ok(())
     }
 
-    /// Update market settings (admin only)
-    pub fn update_market_settings(
+    /// Revoke a methodology's approval so no new credits can use it (admin only)
+    pub fn revoke_methodology(e: &Env, methodology: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        e.storage().persistent().remove(&DataKey::Methodology(methodology));
+
+        Ok(())
+    }
+
+    /// Check whether a methodology is approved for minting
+    pub fn is_methodology_approved(e: &Env, methodology: String) -> bool {
+        e.storage().persistent().has(&DataKey::Methodology(methodology))
+    }
+
+    /// Convert a credit stored with a string verification level to the typed layout (admin only)
+    pub fn migrate_credit(e: &Env, credit_id: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        // Legacy credits were kept in instance storage under their bare id
+        let legacy: LegacyCarbonCredit = e.storage().instance().get(&credit_id)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        let verification_level = VerificationLevel::from_name(e, &legacy.verification_level)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        let status = CreditStatus::from_name(e, &legacy.status)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        let credit = CarbonCredit {
+            id: legacy.id,
+            farmer_address: legacy.farmer_address.clone(),
+            farm_id: legacy.farm_id,
+            season_id: legacy.season_id,
+            carbon_amount: legacy.carbon_amount,
+            verification_level,
+            methodology: legacy.methodology,
+            vintage: legacy.vintage,
+            report_hash: legacy.report_hash,
+            created_at: legacy.created_at,
+            status,
+            coordinates: legacy.coordinates,
+            metadata: legacy.metadata,
+            original_farmer: legacy.farmer_address,
+            royalty_bps: 0,
+        };
+        e.storage().persistent().set(&DataKey::Credit(credit_id.clone()), &credit);
+        e.storage().instance().remove(&credit_id);
+
+        Ok(())
+    }
+
+    /// Rewrite credits stored with a string status to the typed status (admin only)
+    /// Credits that are already migrated are skipped; returns the number rewritten
+    pub fn migrate_statuses(e: &Env, credit_ids: Vec<String>) -> Result<u32, CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if credit_ids.len() > MAX_BATCH_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut migrated = 0;
+        for credit_id in credit_ids.iter() {
+            let key = DataKey::Credit(credit_id);
+            // Inspect the raw record first, since decoding a map with a different
+            // field set into a struct traps instead of returning an error
+            let raw: Map<Symbol, Val> = e.storage().persistent().get(&key)
+                .ok_or(CarbonCreditError::InvalidCreditData)?;
+            let has_string_status = raw.get(symbol_short!("status"))
+                .is_some_and(|status| String::try_from_val(e, &status).is_ok());
+            if !has_string_status {
+                continue;
+            }
+            let legacy = StringStatusCarbonCredit::try_from_val(e, &raw.to_val())
+                .map_err(|_| CarbonCreditError::InvalidCreditData)?;
+            let status = CreditStatus::from_name(e, &legacy.status)
+                .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+            let credit = CarbonCredit {
+                id: legacy.id,
+                farmer_address: legacy.farmer_address.clone(),
+                farm_id: legacy.farm_id,
+                season_id: legacy.season_id,
+                carbon_amount: legacy.carbon_amount,
+                verification_level: legacy.verification_level,
+                methodology: legacy.methodology,
+                vintage: legacy.vintage,
+                report_hash: legacy.report_hash,
+                created_at: legacy.created_at,
+                status,
+                coordinates: legacy.coordinates,
+                metadata: legacy.metadata,
+                original_farmer: legacy.farmer_address,
+                royalty_bps: 0,
+            };
+            e.storage().persistent().set(&key, &credit);
+            extend_ttl(e, &state, &key);
+            migrated += 1;
+        }
+
+        Ok(migrated)
+    }
+
+    /// Allow one more credit to be minted for a farm season that already has one,
+    /// e.g. an amended MRV submission (admin only); the allowance is used by the next mint
+    pub fn allow_season_amendment(
         e: &Env,
-        market_open: bool,
-        min_verification_level: String,
+        farm_id: String,
+        season_id: String,
     ) -> Result<(), CarbonCreditError> {
-        let caller = e.current_contract_address();
-        let state: ContractState = e.storage().instance().get().unwrap();
-        
-        if caller != state.admin {
-            panic_with_error!(e, CarbonCreditError::NotAuthorized);
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if !e.storage().persistent().has(&DataKey::FarmSeason(farm_id.clone(), season_id.clone())) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
         }
+        let key = DataKey::AmendmentAllowed(farm_id, season_id);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
 
-        let mut new_state = state;
-        new_state.market_open = market_open;
-        new_state.min_verification_level = min_verification_level;
-        e.storage().instance().set(&new_state);
+        Ok(())
+    }
+
+    /// Credit minted for a farm's season, if any
+    pub fn get_credit_for_season(e: &Env, farm_id: String, season_id: String) -> Option<String> {
+        e.storage().persistent().get(&DataKey::FarmSeason(farm_id, season_id))
+    }
+
+    /// Release an MRV report hash so it can back a new credit (admin only)
+    /// Reserved for exceptional cases such as a credit minted from a corrected report
+    pub fn invalidate_report_hash(e: &Env, report_hash: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let key = DataKey::ReportHash(report_hash.clone());
+        if !e.storage().persistent().has(&key) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        e.storage().persistent().remove(&key);
+
+        e.events().publish((symbol_short!("admin"), symbol_short!("inv_hash")), report_hash);
 
         Ok(())
     }
 
-    /// Emergency pause for market (admin only)
-    pub fn pause_market(e: &Env) -> Result<(), CarbonCreditError> {
-        let caller = e.current_contract_address();
-        let state: ContractState = e.storage().instance().get().unwrap();
-        
-        if caller != state.admin {
+    /// Get carbon credit details
+    pub fn get_carbon_credit(e: &Env, credit_id: String) -> Option<CarbonCredit> {
+        let key = DataKey::Credit(credit_id);
+        let credit: Option<CarbonCredit> = e.storage().persistent().get(&key);
+        if credit.is_some() {
+            let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+            extend_ttl(e, &state, &key);
+        }
+        credit
+    }
+
+    /// Get several credits at once, in the order requested; unknown ids give `None`
+    /// At most `MAX_PAGE_SIZE` ids may be requested per call
+    pub fn get_credits_by_ids(e: &Env, ids: Vec<String>) -> Vec<Option<CarbonCredit>> {
+        if ids.len() > MAX_PAGE_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut credits = Vec::new(e);
+        for credit_id in ids.iter() {
+            credits.push_back(e.storage().persistent().get(&DataKey::Credit(credit_id)));
+        }
+        credits
+    }
+
+    /// Read a single metadata entry of a credit
+    pub fn get_credit_metadata_value(e: &Env, credit_id: String, key: String) -> Option<String> {
+        let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id))?;
+        credit.metadata.get(key)
+    }
+
+    /// Add or update a single metadata entry of a credit (credit owner, members of an
+    /// owning group, or verifiers)
+    pub fn set_credit_metadata_value(
+        e: &Env,
+        caller: Address,
+        credit_id: String,
+        key: String,
+        value: String,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        caller.require_auth();
+        if caller != credit.farmer_address
+            && !is_group_member(e, &credit.farmer_address, &caller)
+            && !e.storage().persistent().has(&DataKey::Verifier(caller.clone()))
+        {
             panic_with_error!(e, CarbonCreditError::NotAuthorized);
         }
 
-        let mut new_state = state;
-        new_state.market_open = false;
-        e.storage().instance().set(&new_state);
+        if !credit.metadata.contains_key(key.clone()) && credit.metadata.len() >= MAX_METADATA_KEYS {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        credit.metadata.set(key, value);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        e.storage().persistent().set(&credit_key, &credit);
+        extend_ttl(e, &state, &credit_key);
 
         Ok(())
     }
 
-    /// Resume market operations (admin only)
-    pub fn resume_market(e: &Env) -> Result<(), CarbonCreditError> {
-        let caller = e.current_contract_address();
-        let state: ContractState = e.storage().instance().get().unwrap();
-        
-        if caller != state.admin {
+    /// Find the credit minted from an MRV report, by the report's IPFS hash
+    pub fn get_credit_by_report_hash(e: &Env, report_hash: String) -> Option<CarbonCredit> {
+        let credit_id: String = e.storage().persistent().get(&DataKey::ReportHash(report_hash))?;
+        Self::get_carbon_credit(e, credit_id)
+    }
+
+    /// Check whether a credit is past its validity period
+    /// A credit stays valid through the year `vintage + validity_years`
+    pub fn is_credit_expired(e: &Env, credit_id: String) -> Result<bool, CarbonCreditError> {
+        let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        Ok(credit_expired(e, &state, &credit))
+    }
+
+    /// Freeze a disputed credit so it cannot be listed, bought, transferred or retired
+    /// (admin or verifiers)
+    pub fn freeze_credit(e: &Env, manager: Address, credit_id: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        if !e.storage().persistent().has(&DataKey::Credit(credit_id.clone())) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        let key = DataKey::FrozenCredit(credit_id.clone());
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        e.events().publish((symbol_short!("credit"), symbol_short!("freeze")), credit_id);
+
+        Ok(())
+    }
+
+    /// Lift the freeze on a credit (admin or verifiers)
+    pub fn unfreeze_credit(e: &Env, manager: Address, credit_id: String) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        e.storage().persistent().remove(&DataKey::FrozenCredit(credit_id.clone()));
+
+        e.events().publish((symbol_short!("credit"), symbol_short!("unfreeze")), credit_id);
+
+        Ok(())
+    }
+
+    /// Check whether a credit is frozen
+    pub fn is_credit_frozen(e: &Env, credit_id: String) -> bool {
+        e.storage().persistent().has(&DataKey::FrozenCredit(credit_id))
+    }
+
+    /// Flag a credit as suspicious; any address may flag, and the credit cannot trade
+    /// until a verifier resolves the flag
+    pub fn flag_credit(
+        e: &Env,
+        flagger: Address,
+        credit_id: String,
+        reason: String,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        flagger.require_auth();
+
+        let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        if credit.status == CreditStatus::Cancelled || credit.status == CreditStatus::Merged {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        // Only unresolved flags count towards the cap; the oldest resolved flag makes room
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let flagged_key = DataKey::FlaggedCredit(credit_id.clone());
+        let open_flags: u32 = e.storage().persistent().get(&flagged_key).unwrap_or(0);
+        if open_flags >= MAX_FLAGS {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        let flags_key = DataKey::CreditFlags(credit_id.clone());
+        let mut flags: Vec<Flag> = e.storage().persistent().get(&flags_key).unwrap_or(Vec::new(e));
+        if flags.len() >= MAX_FLAGS {
+            flags.pop_front();
+        }
+        flags.push_back(Flag {
+            flagger: flagger.clone(),
+            reason,
+            timestamp: e.ledger().timestamp(),
+        });
+        e.storage().persistent().set(&flags_key, &flags);
+        extend_ttl(e, &state, &flags_key);
+
+        e.storage().persistent().set(&flagged_key, &(open_flags + 1));
+        extend_ttl(e, &state, &flagged_key);
+        record_provenance(e, &state, &credit_id, symbol_short!("flag"), &flagger, 0);
+
+        e.events().publish((symbol_short!("credit"), symbol_short!("flag")), (credit_id, flagger));
+
+        Ok(())
+    }
+
+    /// Resolve the open flags on a credit (verifiers only)
+    /// Upholding the flag cancels the credit; otherwise it may trade again
+    pub fn resolve_flag(
+        e: &Env,
+        verifier: Address,
+        credit_id: String,
+        uphold: bool,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        verifier.require_auth();
+        if !e.storage().persistent().has(&DataKey::Verifier(verifier.clone())) {
             panic_with_error!(e, CarbonCreditError::NotAuthorized);
         }
 
-        let mut new_state = state;
-        new_state.market_open = true;
-        e.storage().instance().set(&new_state);
+        let flagged_key = DataKey::FlaggedCredit(credit_id.clone());
+        if !e.storage().persistent().has(&flagged_key) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        e.storage().persistent().remove(&flagged_key);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        if uphold {
+            let credit_key = DataKey::Credit(credit_id.clone());
+            let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+                .ok_or(CarbonCreditError::InvalidCreditData)?;
+            if credit.status == CreditStatus::Verified {
+                add_level_amount(e, &state, credit.verification_level, -credit.carbon_amount);
+            }
+            credit.status = CreditStatus::Cancelled;
+            unindex_catalog(e, &credit);
+            e.storage().persistent().set(&credit_key, &credit);
+            extend_ttl(e, &state, &credit_key);
+        }
+        record_provenance(e, &state, &credit_id, symbol_short!("resolve"), &verifier, 0);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("resolve")),
+            (credit_id, verifier, uphold),
+        );
 
         Ok(())
     }
-}
 
-#[default_impl]
-#[contractimpl]
-impl FungibleToken for CarbonCreditToken {
-    type ContractType = Base;
-}
+    /// Flags raised against a credit, including the latest resolved ones
+    pub fn get_credit_flags(e: &Env, credit_id: String) -> Vec<Flag> {
+        e.storage().persistent().get(&DataKey::CreditFlags(credit_id)).unwrap_or(Vec::new(e))
+    }
+
+    /// Check whether a credit has an unresolved flag
+    pub fn is_credit_flagged(e: &Env, credit_id: String) -> bool {
+        e.storage().persistent().has(&DataKey::FlaggedCredit(credit_id))
+    }
+
+    /// Refresh the TTL of a credit so it is not archived; callable by anyone
+    pub fn bump_credit_ttl(e: &Env, credit_id: String) -> Result<(), CarbonCreditError> {
+        let key = DataKey::Credit(credit_id);
+        if !e.storage().persistent().has(&key) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        extend_ttl(e, &state, &key);
+
+        Ok(())
+    }
+
+    /// List a farmer's credits, `limit` at a time starting from index `start`
+    pub fn list_credits_by_farmer(
+        e: &Env,
+        farmer: Address,
+        start: u32,
+        limit: u32,
+    ) -> Vec<CarbonCredit> {
+        credit_page(e, &Index::FarmerCredits(farmer), start, limit)
+    }
+
+    /// List the live credits of a vintage year, `limit` at a time starting from index `start`
+    pub fn list_credits_by_vintage(
+        e: &Env,
+        vintage: u32,
+        start: u32,
+        limit: u32,
+    ) -> Vec<CarbonCredit> {
+        credit_page(e, &Index::VintageCredits(vintage), start, limit)
+    }
+
+    /// Number of credit records ever created, including split, merged and distributed ones
+    pub fn get_credit_count(e: &Env) -> u32 {
+        index_len(e, &Index::AllCredits)
+    }
+
+    /// List every credit record for audits, oldest first, `limit` at a time starting from index `start`
+    pub fn list_all_credits(e: &Env, start: u32, limit: u32) -> Vec<CarbonCredit> {
+        credit_page(e, &Index::AllCredits, start, limit)
+    }
+
+    /// Total (minted, retired) tons of a vintage year, for year-over-year impact reporting
+    pub fn get_vintage_stats(e: &Env, vintage: u32) -> (i128, i128) {
+        e.storage().persistent().get(&DataKey::VintageStats(vintage)).unwrap_or((0, 0))
+    }
+
+    /// Tons of live verified credits at each verification level, to gauge market quality
+    pub fn get_level_distribution(e: &Env) -> Map<VerificationLevel, i128> {
+        let mut distribution = Map::new(e);
+        for level in [
+            VerificationLevel::Basic,
+            VerificationLevel::Standard,
+            VerificationLevel::Premium,
+            VerificationLevel::Gold,
+        ] {
+            let amount: i128 = e.storage().persistent().get(&DataKey::LevelAmount(level)).unwrap_or(0);
+            distribution.set(level, amount);
+        }
+        distribution
+    }
+
+    /// Volume-weighted average price per ton of a vintage year's trades, or None if it has none
+    pub fn get_average_price(e: &Env, vintage: u32) -> Option<i128> {
+        let (tons, value): (i128, i128) = e.storage().persistent()
+            .get(&DataKey::VintageTrades(vintage))
+            .unwrap_or((0, 0));
+        if tons == 0 {
+            return None;
+        }
+        Some(checked_mul(e, value, TON) / tons)
+    }
+
+    /// List the live credits of a methodology, `limit` at a time starting from index `start`
+    pub fn list_credits_by_methodology(
+        e: &Env,
+        methodology: String,
+        start: u32,
+        limit: u32,
+    ) -> Vec<CarbonCredit> {
+        credit_page(e, &Index::MethodologyCredits(methodology), start, limit)
+    }
+
+    /// List the credits awaiting verification, `limit` at a time starting from index `start`
+    pub fn list_pending_credits(e: &Env, start: u32, limit: u32) -> Vec<CarbonCredit> {
+        credit_page(e, &Index::PendingCredits, start, limit)
+    }
+
+    /// Total tons minted in a one-degree grid cell, keyed like `-12_45` (latitude_longitude)
+    pub fn get_region_stats(e: &Env, region_key: String) -> i128 {
+        e.storage().persistent().get(&DataKey::RegionTotal(region_key)).unwrap_or(0)
+    }
+
+    /// Keys of every grid cell credits have been minted in
+    pub fn list_regions(e: &Env) -> Vec<String> {
+        e.storage().persistent().get(&DataKey::Regions).unwrap_or(Vec::new(e))
+    }
+
+    /// List carbon credit for sale on marketplace
+    /// Enables farmers to monetize their environmental contributions
+    /// `list_amount` tons are escrowed, so a farmer can sell part of a credit and keep the rest
+    /// With a `buyer_restriction` only buyers holding that role may fill the order
+    /// `price_asset` is an admin-approved token to settle in instead of the default payment token
+    pub fn list_for_sale(
+        e: &Env,
+        credit_id: String,
+        price_per_ton: i128,
+        list_amount: i128,
+        buyer_restriction: Option<Symbol>,
+        price_asset: Option<Address>,
+        member: Option<Address>,
+    ) -> Result<String, CarbonCreditError> {
+        let order_id = create_sell_order(e, credit_id, price_per_ton, list_amount, "Sell", member);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        if let Some(role) = buyer_restriction {
+            let restriction_key = DataKey::BuyerRestriction(order_id.clone());
+            e.storage().persistent().set(&restriction_key, &role);
+            extend_ttl(e, &state, &restriction_key);
+        }
+        if let Some(asset) = price_asset {
+            if asset != state.payment_token {
+                if !e.storage().persistent().has(&DataKey::PriceAsset(asset.clone())) {
+                    panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+                }
+                let asset_key = DataKey::OrderAsset(order_id.clone());
+                e.storage().persistent().set(&asset_key, &asset);
+                extend_ttl(e, &state, &asset_key);
+            }
+        }
+
+        Ok(order_id)
+    }
+
+    /// Token contract an order settles in
+    pub fn get_order_asset(e: &Env, order_id: String) -> Address {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        order_asset(e, &state, &order_id)
+    }
+
+    /// Approve a token contract, such as USDC, for settling orders (admin only)
+    pub fn approve_price_asset(e: &Env, asset: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let key = DataKey::PriceAsset(asset);
+        e.storage().persistent().set(&key, &true);
+        extend_ttl(e, &state, &key);
+
+        Ok(())
+    }
+
+    /// Stop accepting a token contract for new orders; existing orders still settle in it
+    /// (admin only)
+    pub fn revoke_price_asset(e: &Env, asset: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        e.storage().persistent().remove(&DataKey::PriceAsset(asset));
+
+        Ok(())
+    }
+
+    /// Check whether a token contract is approved for settling orders
+    pub fn is_price_asset_approved(e: &Env, asset: Address) -> bool {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        asset == state.payment_token || e.storage().persistent().has(&DataKey::PriceAsset(asset))
+    }
+
+    /// Role a buyer must hold to fill an order, if the seller restricted it
+    pub fn get_buyer_restriction(e: &Env, order_id: String) -> Option<Symbol> {
+        e.storage().persistent().get(&DataKey::BuyerRestriction(order_id))
+    }
+
+    /// List a carbon credit in a Dutch auction
+    /// The price decays linearly from `start_price` to `floor_price` over `duration_secs`,
+    /// letting the market discover a fair value for farmers who cannot price credits themselves
+    pub fn list_for_auction(
+        e: &Env,
+        credit_id: String,
+        start_price: i128,
+        floor_price: i128,
+        duration_secs: u64,
+        list_amount: i128,
+        member: Option<Address>,
+    ) -> Result<String, CarbonCreditError> {
+        if floor_price <= 0 || start_price < floor_price || duration_secs == 0 {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let order_id = create_sell_order(e, credit_id, start_price, list_amount, "Auction", member);
+
+        let auction = Auction {
+            start_price,
+            floor_price,
+            start_time: e.ledger().timestamp(),
+            duration_secs,
+        };
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let auction_key = DataKey::Auction(order_id.clone());
+        e.storage().persistent().set(&auction_key, &auction);
+        extend_ttl(e, &state, &auction_key);
+
+        Ok(order_id)
+    }
+
+    /// Current price per ton of an auction order, or `None` if the order is not an auction
+    pub fn current_auction_price(e: &Env, order_id: String) -> Option<i128> {
+        let auction: Auction = e.storage().persistent().get(&DataKey::Auction(order_id))?;
+        Some(auction.price_at(e, e.ledger().timestamp()))
+    }
+
+    /// Get market order details
+    pub fn get_market_order(e: &Env, order_id: String) -> Option<MarketOrder> {
+        e.storage().persistent().get(&DataKey::Order(order_id))
+    }
+
+    /// List active market orders, `limit` at a time starting from index `start`
+    pub fn list_active_orders(e: &Env, start: u32, limit: u32) -> Vec<MarketOrder> {
+        let mut orders = Vec::new(e);
+        for order_id in index_page(e, &Index::ActiveOrders, start, limit).iter() {
+            if let Some(order) = e.storage().persistent().get::<_, MarketOrder>(&DataKey::Order(order_id)) {
+                if order.status == String::from_str(e, "Active") {
+                    orders.push_back(order);
+                }
+            }
+        }
+        orders
+    }
+
+    /// List a seller's active orders, `limit` at a time starting from index `start`
+    pub fn get_seller_active_listings(e: &Env, seller: Address, start: u32, limit: u32) -> Vec<MarketOrder> {
+        let mut orders = Vec::new(e);
+        for order_id in index_page(e, &Index::SellerOrders(seller), start, limit).iter() {
+            if let Some(order) = e.storage().persistent().get(&DataKey::Order(order_id)) {
+                orders.push_back(order);
+            }
+        }
+        orders
+    }
+
+    /// Total value of a seller's active orders: the unsold amount of each at its current price
+    pub fn get_seller_active_value(e: &Env, seller: Address) -> i128 {
+        let mut value: i128 = 0;
+        for order_id in index_ids(e, &Index::SellerOrders(seller)).iter() {
+            if let Some(order) = e.storage().persistent().get::<_, MarketOrder>(&DataKey::Order(order_id)) {
+                value = checked_add(e, value, trade_price(e, order.amount, order_price(e, &order)));
+            }
+        }
+        value
+    }
+
+    /// List every market order whatever its status, oldest first, `limit` at a time starting
+    /// from index `start`; executed amounts and prices are available from `get_order_fill`
+    pub fn list_orders(e: &Env, start: u32, limit: u32) -> Vec<MarketOrder> {
+        let mut orders = Vec::new(e);
+        for order_id in index_page(e, &Index::AllOrders, start, limit).iter() {
+            if let Some(order) = e.storage().persistent().get(&DataKey::Order(order_id)) {
+                orders.push_back(order);
+            }
+        }
+        orders
+    }
+
+    /// Preview the cost of buying `amount` from an order at its current price,
+    /// including the auction decay, without changing any state
+    /// The buyer pays `total`; the fee and royalty come out of the seller's share
+    pub fn simulate_buy(e: &Env, order_id: String, amount: i128) -> Result<BuyQuote, CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let order: MarketOrder = e.storage().persistent().get(&DataKey::Order(order_id))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        if order.status != String::from_str(e, "Active") {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        if amount <= 0 {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        if amount > order.amount {
+            panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+        }
+        let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(order.credit_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        let base_price = trade_price(e, amount, order_price(e, &order));
+        let (fee, royalty) = trade_cuts(e, &state, &order, &credit, base_price);
+
+        Ok(BuyQuote {
+            base_price,
+            fee,
+            royalty,
+            total: base_price,
+        })
+    }
+
+    /// Executed fills of an order, if it has been bought from
+    pub fn get_order_fill(e: &Env, order_id: String) -> Option<OrderFill> {
+        e.storage().persistent().get(&DataKey::OrderFill(order_id))
+    }
+
+    /// Get the active sell orders of a credit, cheapest first at their current price
+    /// (auction orders sort by their decayed price), at most `limit` of them
+    /// Only the first page of the credit's orders is scanned so the sort stays bounded
+    pub fn get_order_book(e: &Env, credit_id: String, limit: u32) -> Vec<MarketOrder> {
+        let credit_orders = index_page(e, &Index::CreditOrders(credit_id), 0, MAX_PAGE_SIZE);
+
+        // Insertion sort by price; orders at the same price keep their index order
+        let mut book: Vec<MarketOrder> = Vec::new(e);
+        let mut prices: Vec<i128> = Vec::new(e);
+        for order_id in credit_orders.iter() {
+            let order: MarketOrder = match e.storage().persistent().get(&DataKey::Order(order_id)) {
+                Some(order) => order,
+                None => continue,
+            };
+            if order.status != String::from_str(e, "Active") {
+                continue;
+            }
+            let price = order_price(e, &order);
+            let mut position = book.len();
+            while position > 0 && prices.get_unchecked(position - 1) > price {
+                position -= 1;
+            }
+            book.insert(position, order);
+            prices.insert(position, price);
+        }
+
+        let limit = limit.min(MAX_PAGE_SIZE);
+        if book.len() > limit {
+            book = book.slice(0..limit);
+        }
+        book
+    }
+
+    /// Amount of a credit tied up in its active orders and pending escrows
+    pub fn get_reserved_amount(e: &Env, credit_id: String) -> i128 {
+        reserved_amount(e, &credit_id)
+    }
+
+    /// Amount of a credit that is not tied up in active orders or pending escrows and may still be listed
+    pub fn get_available_amount(e: &Env, credit_id: String) -> Result<i128, CarbonCreditError> {
+        let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        Ok(credit.carbon_amount - reserved_amount(e, &credit_id))
+    }
+
+    /// Get a credit's executed trades, oldest first, `limit` at a time starting from `start`
+    pub fn get_price_history(e: &Env, credit_id: String, start: u32, limit: u32) -> Vec<TradePoint> {
+        let history: Vec<TradePoint> = e.storage().persistent()
+            .get(&DataKey::PriceHistory(credit_id))
+            .unwrap_or(Vec::new(e));
+        let end = history.len().min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+        if start >= end {
+            return Vec::new(e);
+        }
+        history.slice(start..end)
+    }
+
+    /// Owners of a credit with the time each became owner, oldest first
+    pub fn get_ownership_history(e: &Env, credit_id: String) -> Vec<(Address, u64)> {
+        e.storage().persistent().get(&DataKey::CreditOwners(credit_id)).unwrap_or(Vec::new(e))
+    }
+
+    /// Get a credit's lifecycle history, oldest first, `limit` entries at a time starting from `start`
+    pub fn get_credit_provenance(e: &Env, credit_id: String, start: u32, limit: u32) -> Vec<ProvenanceEntry> {
+        let count: u32 = e.storage().persistent().get(&DataKey::ProvenanceCount(credit_id.clone())).unwrap_or(0);
+        let end = count.min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+        let mut entries = Vec::new(e);
+        for i in start..end {
+            if let Some(entry) = e.storage().persistent().get(&DataKey::Provenance(credit_id.clone(), i)) {
+                entries.push_back(entry);
+            }
+        }
+        entries
+    }
+
+    /// Get the latest trades across all credits, newest first
+    pub fn get_recent_trades(e: &Env, limit: u32) -> Vec<TradeRecord> {
+        let trades: Vec<TradeRecord> = e.storage().persistent()
+            .get(&DataKey::RecentTrades)
+            .unwrap_or(Vec::new(e));
+        let mut recent = Vec::new(e);
+        for trade in trades.iter().rev().take(limit.min(MAX_PAGE_SIZE) as usize) {
+            recent.push_back(trade);
+        }
+        recent
+    }
+
+    /// Buy carbon credits from marketplace
+    /// Enables investors to support sustainable farming
+    /// `max_total_price` protects the buyer from auction and repricing moves
+    /// between submission and execution
+    pub fn buy_carbon_credits(
+        e: &Env,
+        order_id: String,
+        buyer_address: Address,
+        amount: i128,
+        max_total_price: i128,
+    ) -> Result<(), CarbonCreditError> {
+        buyer_address.require_auth();
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+
+        let (order, price_per_ton) = fill_order(e, &state, &order_id, &buyer_address, amount);
+        let total_price = trade_price(e, amount, price_per_ton);
+        if total_price > max_total_price {
+            panic_with_error!(e, CarbonCreditError::SlippageExceeded);
+        }
+        settle_trade(e, state, &order, &buyer_address, &buyer_address, amount, price_per_ton);
+
+        e.events().publish(
+            (symbol_short!("market"), symbol_short!("buy")),
+            (order_id, buyer_address, amount, total_price),
+        );
+
+        Ok(())
+    }
+
+    /// Reserve credits from an order and lock the buyer's payment in the contract
+    /// Used for delayed or fiat-backed settlement; the trade completes once an admin
+    /// or verifier settles the escrow, or is unwound by a refund
+    pub fn create_escrow_order(
+        e: &Env,
+        order_id: String,
+        buyer: Address,
+        amount: i128,
+    ) -> Result<String, CarbonCreditError> {
+        buyer.require_auth();
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+
+        let (order, price_per_ton) = fill_order(e, &state, &order_id, &buyer, amount);
+        let total_price = trade_price(e, amount, price_per_ton);
+        token::Client::new(e, &order_asset(e, &state, &order_id))
+            .transfer(&buyer, &e.current_contract_address(), &total_price);
+
+        // The escrowed amount stays reserved on the credit until the escrow settles or is refunded
+        let reserved = reserved_amount(e, &order.credit_id);
+        set_reserved_amount(e, &state, &order.credit_id, reserved + amount);
+
+        let count: u64 = e.storage().instance().get(&DataKey::EscrowCount).unwrap_or(0) + 1;
+        e.storage().instance().set(&DataKey::EscrowCount, &count);
+        let mut escrow_id = IdBuilder::new();
+        escrow_id.push_bytes(e, b"ESCROW_");
+        escrow_id.push_u64(e, count);
+        let escrow_id = escrow_id.build(e);
+
+        let escrow = Escrow {
+            id: escrow_id.clone(),
+            order_id,
+            buyer: buyer.clone(),
+            amount,
+            price_per_ton,
+            status: String::from_str(e, "Pending"),
+            created_at: e.ledger().timestamp(),
+        };
+        let escrow_key = DataKey::Escrow(escrow_id.clone());
+        e.storage().persistent().set(&escrow_key, &escrow);
+        extend_ttl(e, &state, &escrow_key);
+
+        e.events().publish(
+            (symbol_short!("escrow"), symbol_short!("create")),
+            (escrow_id.clone(), buyer, amount, total_price),
+        );
+
+        Ok(escrow_id)
+    }
+
+    /// Release an escrowed payment to the seller and the reserved credits to the buyer
+    /// (admin or verifiers)
+    pub fn settle_escrow(e: &Env, manager: Address, escrow_id: String) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        let escrow_key = DataKey::Escrow(escrow_id.clone());
+        let mut escrow = load_pending_escrow(e, &escrow_key);
+        let order: MarketOrder = e.storage().persistent().get(&DataKey::Order(escrow.order_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        escrow.status = String::from_str(e, "Settled");
+        e.storage().persistent().set(&escrow_key, &escrow);
+        extend_ttl(e, &state, &escrow_key);
+        let reserved = reserved_amount(e, &order.credit_id);
+        set_reserved_amount(e, &state, &order.credit_id, reserved - escrow.amount);
+
+        settle_trade(
+            e,
+            state,
+            &order,
+            &e.current_contract_address(),
+            &escrow.buyer,
+            escrow.amount,
+            escrow.price_per_ton,
+        );
+
+        e.events().publish(
+            (symbol_short!("escrow"), symbol_short!("settle")),
+            (escrow_id, escrow.buyer, escrow.amount),
+        );
+
+        Ok(())
+    }
+
+    /// Refund an escrowed payment to the buyer and return the reserved credits
+    /// (admin or verifiers)
+    /// The credits go back on the order while it is active, otherwise to the seller
+    pub fn refund_escrow(e: &Env, manager: Address, escrow_id: String) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let mut state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_admin_or_verifier(e, &state, &manager);
+
+        let escrow_key = DataKey::Escrow(escrow_id.clone());
+        let mut escrow = load_pending_escrow(e, &escrow_key);
+        let order_key = DataKey::Order(escrow.order_id.clone());
+        let mut order: MarketOrder = e.storage().persistent().get(&order_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        escrow.status = String::from_str(e, "Refunded");
+        e.storage().persistent().set(&escrow_key, &escrow);
+        extend_ttl(e, &state, &escrow_key);
+
+        token::Client::new(e, &order_asset(e, &state, &escrow.order_id)).transfer(
+            &e.current_contract_address(),
+            &escrow.buyer,
+            &trade_price(e, escrow.amount, escrow.price_per_ton),
+        );
+
+        // The escrowed amount stays reserved when it goes back on the order, and is
+        // released along with the credits otherwise
+        if order.status == String::from_str(e, "Active") {
+            order.amount = checked_add(e, order.amount, escrow.amount);
+            e.storage().persistent().set(&order_key, &order);
+            extend_ttl(e, &state, &order_key);
+        } else {
+            let reserved = reserved_amount(e, &order.credit_id);
+            set_reserved_amount(e, &state, &order.credit_id, reserved - escrow.amount);
+            move_credits(e, &e.current_contract_address(), &order.seller_address, escrow.amount);
+            release_listing_fee(e, &state, &order.id, escrow.amount, false);
+            state.total_credits_listed -= escrow.amount;
+            e.storage().instance().set(&DataKey::State, &state);
+        }
+
+        e.events().publish(
+            (symbol_short!("escrow"), symbol_short!("refund")),
+            (escrow_id, escrow.buyer, escrow.amount),
+        );
+
+        Ok(())
+    }
+
+    /// Get escrowed purchase details
+    pub fn get_escrow(e: &Env, escrow_id: String) -> Option<Escrow> {
+        e.storage().persistent().get(&DataKey::Escrow(escrow_id))
+    }
+
+    /// Cancel an active sell order and return the unsold credits to the seller
+    pub fn cancel_order(e: &Env, order_id: String, member: Option<Address>) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let mut order: MarketOrder = e.storage().persistent().get(&DataKey::Order(order_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        // Only the seller may withdraw their listing
+        require_owner_auth(e, &order.seller_address, &member);
+
+        if order.status == String::from_str(e, "Filled")
+            || order.status == String::from_str(e, "Cancelled")
+        {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        // Release the remaining escrow back to the seller
+        move_credits(e, &e.current_contract_address(), &order.seller_address, order.amount);
+        let mut state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let reserved = reserved_amount(e, &order.credit_id);
+        set_reserved_amount(e, &state, &order.credit_id, reserved - order.amount);
+        state.total_credits_listed -= order.amount;
+        e.storage().instance().set(&DataKey::State, &state);
+        record_provenance(e, &state, &order.credit_id, symbol_short!("delist"), &order.seller_address, order.amount);
+
+        // The unsold amount forfeits its share of the listing fee
+        release_listing_fee(e, &state, &order_id, order.amount, false);
+
+        order.status = String::from_str(e, "Cancelled");
+        e.storage().persistent().set(&DataKey::Order(order_id.clone()), &order);
+        index_remove(e, &Index::CreditOrders(order.credit_id.clone()), &order_id);
+        index_remove(e, &Index::ActiveOrders, &order_id);
+        index_remove(e, &Index::SellerOrders(order.seller_address.clone()), &order_id);
+
+        Ok(())
+    }
+
+    /// Reprice an active fixed-price order without cancelling it (seller only)
+    pub fn update_order_price(
+        e: &Env,
+        order_id: String,
+        new_price: i128,
+        member: Option<Address>,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let order_key = DataKey::Order(order_id.clone());
+        let mut order: MarketOrder = e.storage().persistent().get(&order_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        require_owner_auth(e, &order.seller_address, &member);
+
+        // Auction prices follow their decay curve and cannot be set directly
+        if order.status != String::from_str(e, "Active")
+            || e.storage().persistent().has(&DataKey::Auction(order_id.clone()))
+            || new_price <= 0
+        {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        order.price_per_ton = new_price;
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        e.storage().persistent().set(&order_key, &order);
+        extend_ttl(e, &state, &order_key);
+        record_provenance(e, &state, &order.credit_id, symbol_short!("reprice"), &order.seller_address, order.amount);
+
+        e.events().publish(
+            (symbol_short!("market"), symbol_short!("reprice")),
+            (order_id, new_price),
+        );
+
+        Ok(())
+    }
+
+    /// Transfer a whole carbon credit record and its tokens to a new owner
+    /// Lets farmers gift credits or consolidate them into a cooperative off-market
+    pub fn transfer_credit_ownership(
+        e: &Env,
+        credit_id: String,
+        new_owner: Address,
+        member: Option<Address>,
+    ) -> Result<(), CarbonCreditError> {
+        require_not_paused(e);
+
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+
+        assert_credit_mutable(e, &credit);
+
+        // Verify caller owns the credit
+        let previous_owner = credit.farmer_address.clone();
+        require_owner_auth(e, &previous_owner, &member);
+        require_not_blocked(e, &previous_owner);
+        require_not_blocked(e, &new_owner);
+        require_not_frozen(e, &credit_id);
+        require_unlocked(e, &credit_id);
+
+        // Active orders and pending escrows are paid out to the current owner, so a
+        // credit with reserved tons cannot change hands
+        if reserved_amount(e, &credit_id) > 0 {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        // Move the tokens backing the credit along with the record
+        move_credits(e, &previous_owner, &new_owner, credit.carbon_amount);
+
+        credit.farmer_address = new_owner.clone();
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        e.storage().persistent().set(&credit_key, &credit);
+        extend_ttl(e, &state, &credit_key);
+
+        index_remove(e, &Index::FarmerCredits(previous_owner.clone()), &credit_id);
+        index_push(e, &state, &Index::FarmerCredits(new_owner.clone()), &credit_id);
+        record_owner(e, &state, &credit_id, &new_owner);
+        record_provenance(e, &state, &credit_id, symbol_short!("transfer"), &new_owner, credit.carbon_amount);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("transfer")),
+            (credit_id, previous_owner, new_owner),
+        );
+
+        Ok(())
+    }
+
+    /// Split part of a credit off into a new credit record
+    /// The new record keeps the original's vintage, methodology and metadata; token
+    /// balances are unchanged since both records remain backed 1:1 by the owner's tokens
+    pub fn split_credit(
+        e: &Env,
+        credit_id: String,
+        split_amount: i128,
+        member: Option<Address>,
+    ) -> Result<String, CarbonCreditError> {
+        require_not_paused(e);
+
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        assert_credit_mutable(e, &credit);
+
+        // Verify caller owns the credit
+        require_owner_auth(e, &credit.farmer_address, &member);
+        require_not_frozen(e, &credit_id);
+
+        if split_amount <= 0 || split_amount >= credit.carbon_amount {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        // Only the tons not held by active orders or pending escrows can be split off
+        if split_amount > credit.carbon_amount - reserved_amount(e, &credit_id) {
+            panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+        }
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        require_aligned_amount(e, &state, split_amount);
+
+        let split_id = derive_credit_id(e, &credit_id, b"_SPLIT_");
+
+        let mut split = credit.clone();
+        split.id = split_id.clone();
+        split.carbon_amount = split_amount;
+        split.created_at = e.ledger().timestamp();
+        credit.carbon_amount -= split_amount;
+
+        e.storage().persistent().set(&credit_key, &credit);
+        extend_ttl(e, &state, &credit_key);
+        let split_key = DataKey::Credit(split_id.clone());
+        e.storage().persistent().set(&split_key, &split);
+        extend_ttl(e, &state, &split_key);
+        index_push(e, &state, &Index::FarmerCredits(split.farmer_address.clone()), &split_id);
+        index_catalog(e, &state, &split);
+        index_push(e, &state, &Index::AllCredits, &split_id);
+        record_owner(e, &state, &split_id, &split.farmer_address);
+        set_unlock_at(e, &state, &split_id, unlock_at(e, &credit_id));
+        record_provenance(e, &state, &credit_id, symbol_short!("split"), &credit.farmer_address, split_amount);
+        record_provenance(e, &state, &split_id, symbol_short!("split"), &split.farmer_address, split_amount);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("split")),
+            (credit_id, split_id.clone(), split_amount),
+        );
+
+        Ok(split_id)
+    }
+
+    /// Distribute parts of a pooled credit to cooperative members in one call
+    /// Each recipient gets a child credit and the tokens backing it; a credit distributed
+    /// in full no longer backs tokens of its own and is marked Merged. The call is atomic
+    pub fn distribute_credits(
+        e: &Env,
+        credit_id: String,
+        recipients: Vec<(Address, i128)>,
+        member: Option<Address>,
+    ) -> Result<Vec<String>, CarbonCreditError> {
+        require_not_paused(e);
+
+        let credit_key = DataKey::Credit(credit_id.clone());
+        let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        assert_credit_mutable(e, &credit);
+
+        // Verify caller owns the credit
+        let owner = credit.farmer_address.clone();
+        require_owner_auth(e, &owner, &member);
+        require_not_blocked(e, &owner);
+        require_not_frozen(e, &credit_id);
+        require_unlocked(e, &credit_id);
+
+        if recipients.is_empty() || recipients.len() > MAX_BATCH_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        // Only the tons not held by active orders or pending escrows can be distributed
+        let reserved = reserved_amount(e, &credit_id);
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let mut child_ids = Vec::new(e);
+        for (recipient, amount) in recipients.iter() {
+            require_not_blocked(e, &recipient);
+            if amount <= 0 || amount > credit.carbon_amount {
+                panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+            }
+            if amount > credit.carbon_amount - reserved {
+                panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+            }
+            require_aligned_amount(e, &state, amount);
+
+            let child_id = derive_credit_id(e, &credit_id, b"_DIST_");
+            let mut child = credit.clone();
+            child.id = child_id.clone();
+            child.farmer_address = recipient.clone();
+            child.carbon_amount = amount;
+            child.created_at = e.ledger().timestamp();
+            credit.carbon_amount -= amount;
+
+            move_credits(e, &owner, &recipient, amount);
+
+            let child_key = DataKey::Credit(child_id.clone());
+            e.storage().persistent().set(&child_key, &child);
+            extend_ttl(e, &state, &child_key);
+            index_push(e, &state, &Index::FarmerCredits(recipient.clone()), &child_id);
+            index_catalog(e, &state, &child);
+            index_push(e, &state, &Index::AllCredits, &child_id);
+            record_owner(e, &state, &child_id, &recipient);
+            record_provenance(e, &state, &child_id, symbol_short!("distrib"), &recipient, amount);
+            record_provenance(e, &state, &credit_id, symbol_short!("distrib"), &recipient, amount);
+
+            child_ids.push_back(child_id);
+        }
+
+        if credit.carbon_amount == 0 {
+            credit.status = CreditStatus::Merged;
+            index_remove(e, &Index::FarmerCredits(owner.clone()), &credit_id);
+            unindex_catalog(e, &credit);
+        }
+        e.storage().persistent().set(&credit_key, &credit);
+        extend_ttl(e, &state, &credit_key);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("distrib")),
+            (credit_id, child_ids.clone()),
+        );
+
+        Ok(child_ids)
+    }
+
+    /// Merge several credits of one owner into a single new credit
+    /// All credits must share vintage, methodology, verification level and status;
+    /// the originals are marked Merged and the new credit holds their combined amount
+    pub fn merge_credits(
+        e: &Env,
+        credit_ids: Vec<String>,
+        member: Option<Address>,
+    ) -> Result<String, CarbonCreditError> {
+        require_not_paused(e);
+
+        if credit_ids.len() < 2 || credit_ids.len() > MAX_BATCH_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let first_id = credit_ids.get(0).unwrap();
+        let mut merged: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(first_id.clone()))
+            .ok_or(CarbonCreditError::InvalidCreditData)?;
+        assert_credit_mutable(e, &merged);
+
+        // Verify caller owns the credits
+        require_owner_auth(e, &merged.farmer_address, &member);
+
+        if merged.status != CreditStatus::Pending
+            && merged.status != CreditStatus::Verified
+        {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let farmer_credits_key = Index::FarmerCredits(merged.farmer_address.clone());
+        let mut total: i128 = 0;
+        let mut merged_unlock_at: u64 = 0;
+        for credit_id in credit_ids.iter() {
+            let credit_key = DataKey::Credit(credit_id.clone());
+            let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+                .ok_or(CarbonCreditError::InvalidCreditData)?;
+            require_not_frozen(e, &credit_id);
+
+            // A repeated id is caught here since its first occurrence is already Merged
+            if credit.farmer_address != merged.farmer_address
+                || credit.vintage != merged.vintage
+                || credit.methodology != merged.methodology
+                || credit.verification_level != merged.verification_level
+                || credit.status != merged.status
+                || credit.original_farmer != merged.original_farmer
+                || credit.royalty_bps != merged.royalty_bps
+            {
+                panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+            }
+
+            // Active orders and pending escrows refer to the credit by id, so a credit
+            // with reserved tons cannot be merged away
+            if reserved_amount(e, &credit_id) > 0 {
+                panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+            }
+
+            total = checked_add(e, total, credit.carbon_amount);
+            merged_unlock_at = merged_unlock_at.max(unlock_at(e, &credit_id));
+            credit.status = CreditStatus::Merged;
+            e.storage().persistent().set(&credit_key, &credit);
+            extend_ttl(e, &state, &credit_key);
+            index_remove(e, &farmer_credits_key, &credit_id);
+            unindex_catalog(e, &credit);
+            record_provenance(e, &state, &credit_id, symbol_short!("merge"), &credit.farmer_address, credit.carbon_amount);
+        }
+
+        let merged_id = derive_credit_id(e, &first_id, b"_MERGE_");
+        merged.id = merged_id.clone();
+        merged.carbon_amount = total;
+        merged.created_at = e.ledger().timestamp();
+
+        let merged_key = DataKey::Credit(merged_id.clone());
+        e.storage().persistent().set(&merged_key, &merged);
+        extend_ttl(e, &state, &merged_key);
+        index_push(e, &state, &farmer_credits_key, &merged_id);
+        index_catalog(e, &state, &merged);
+        index_push(e, &state, &Index::AllCredits, &merged_id);
+        record_owner(e, &state, &merged_id, &merged.farmer_address);
+        set_unlock_at(e, &state, &merged_id, merged_unlock_at);
+        record_provenance(e, &state, &merged_id, symbol_short!("merge"), &merged.farmer_address, total);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("merge")),
+            (merged_id.clone(), credit_ids, total),
+        );
+
+        Ok(merged_id)
+    }
+
+    /// Retire carbon credits (permanent removal from circulation)
+    /// Used when credits are used for offsetting emissions; the retiree must own the
+    /// credit, which buyers do for the credit carved off for them by their purchase
+    pub fn retire_credits(
+        e: &Env,
+        credit_id: String,
+        retiree: Address,
+        amount: i128,
+        reason: RetirementReason,
+        note: Option<String>,
+    ) -> Result<String, CarbonCreditError> {
+        require_not_paused(e);
+
+        retiree.require_auth();
+
+        Ok(retire_credit(e, credit_id, &retiree, amount, reason, note))
+    }
+
+    /// Retire amounts from several credits at once, e.g. for annual offset reporting
+    /// Issues one certificate per entry plus an aggregate certificate referencing them,
+    /// whose id is returned; the batch is atomic
+    pub fn retire_credits_batch(
+        e: &Env,
+        retiree: Address,
+        retirements: Vec<RetireRequest>,
+        reason: RetirementReason,
+        note: Option<String>,
+    ) -> Result<String, CarbonCreditError> {
+        require_not_paused(e);
+
+        retiree.require_auth();
+
+        if retirements.is_empty() || retirements.len() > MAX_BATCH_SIZE {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut certificate_ids = Vec::new(e);
+        let mut total_amount: i128 = 0;
+        for request in retirements.iter() {
+            total_amount = checked_add(e, total_amount, request.amount);
+            certificate_ids.push_back(retire_credit(
+                e,
+                request.credit_id,
+                &retiree,
+                request.amount,
+                reason,
+                note.clone(),
+            ));
+        }
+
+        let count: u64 = e.storage().instance().get(&DataKey::BatchRetirementCount).unwrap_or(0) + 1;
+        e.storage().instance().set(&DataKey::BatchRetirementCount, &count);
+        let mut aggregate_id = IdBuilder::new();
+        aggregate_id.push_bytes(e, b"BATCH_CERT_");
+        aggregate_id.push_u64(e, count);
+        let aggregate_id = aggregate_id.build(e);
+
+        let aggregate = AggregateCertificate {
+            id: aggregate_id.clone(),
+            retiree,
+            certificate_ids,
+            total_amount,
+            reason,
+            note,
+            retired_at: e.ledger().timestamp(),
+        };
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let aggregate_key = DataKey::AggregateCertificate(aggregate_id.clone());
+        e.storage().persistent().set(&aggregate_key, &aggregate);
+        extend_ttl(e, &state, &aggregate_key);
+
+        e.events().publish(
+            (symbol_short!("credit"), symbol_short!("retire_b")),
+            (aggregate_id.clone(), total_amount),
+        );
+
+        Ok(aggregate_id)
+    }
+
+    /// List the ids of the retirement certificates issued for a purpose, oldest first,
+    /// `limit` at a time starting from index `start`
+    pub fn get_retirements_by_reason(
+        e: &Env,
+        reason: RetirementReason,
+        start: u32,
+        limit: u32,
+    ) -> Vec<String> {
+        index_page(e, &Index::ReasonRetirements(reason), start, limit)
+    }
+
+    /// Get an aggregate retirement certificate by id
+    pub fn get_aggregate_certificate(e: &Env, certificate_id: String) -> Option<AggregateCertificate> {
+        e.storage().persistent().get(&DataKey::AggregateCertificate(certificate_id))
+    }
+
+    /// Farmers ranked by lifetime tons minted, at most `limit` of them
+    /// Only the top `LEADERBOARD_SIZE` farmers are tracked, so larger limits return no more
+    pub fn get_top_farmers(e: &Env, limit: u32) -> Vec<(Address, i128)> {
+        let top: Vec<(Address, i128)> = e.storage().persistent().get(&DataKey::TopFarmers).unwrap_or(Vec::new(e));
+        let limit = limit.min(top.len());
+        top.slice(0..limit)
+    }
+
+    /// Lifetime payments a farmer has received from credit sales and resale royalties,
+    /// net of protocol fees
+    pub fn get_farmer_earnings(e: &Env, farmer: Address) -> i128 {
+        e.storage().persistent().get(&DataKey::FarmerEarnings(farmer)).unwrap_or(0)
+    }
+
+    /// Get a retirement certificate by id
+    pub fn get_retirement_certificate(e: &Env, certificate_id: String) -> Option<RetirementCertificate> {
+        e.storage().persistent().get(&DataKey::Certificate(certificate_id))
+    }
+
+    /// List the retirement certificates issued against a credit, oldest first
+    pub fn list_retirements_by_credit(e: &Env, credit_id: String) -> Vec<RetirementCertificate> {
+        let retirements: Vec<String> = e.storage().persistent()
+            .get(&DataKey::CreditRetirements(credit_id))
+            .unwrap_or(Vec::new(e));
+
+        let mut certificates = Vec::new(e);
+        for certificate_id in retirements.iter() {
+            if let Some(certificate) = e.storage().persistent().get(&DataKey::Certificate(certificate_id)) {
+                certificates.push_back(certificate);
+            }
+        }
+        certificates
+    }
+
+    /// List the retirement certificates issued to a retiree, `limit` at a time
+    /// starting from index `start`
+    pub fn list_certificates_by_retiree(
+        e: &Env,
+        retiree: Address,
+        start: u32,
+        limit: u32,
+    ) -> Vec<RetirementCertificate> {
+        let mut certificates = Vec::new(e);
+        for certificate_id in index_page(e, &Index::RetireeCertificates(retiree), start, limit).iter() {
+            if let Some(certificate) = e.storage().persistent().get(&DataKey::Certificate(certificate_id)) {
+                certificates.push_back(certificate);
+            }
+        }
+        certificates
+    }
+
+    /// Get contract statistics for transparency
+    pub fn get_contract_stats(e: &Env) -> ContractState {
+        e.storage().instance().get(&DataKey::State).unwrap()
+    }
+
+    /// Every admin-tunable parameter along with the admin and treasury addresses
+    pub fn get_config(e: &Env) -> ContractConfig {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        ContractConfig {
+            admin: state.admin,
+            treasury: state.treasury,
+            payment_token: state.payment_token,
+            market_open: state.market_open,
+            min_verification_level: state.min_verification_level,
+            contract_paused: state.contract_paused,
+            tokens_frozen: state.tokens_frozen,
+            require_buyer_kyc: state.require_buyer_kyc,
+            fee_bps: state.fee_bps,
+            royalty_bps: state.royalty_bps,
+            rounding_mode: state.rounding_mode,
+            list_fee: state.list_fee,
+            mint_rate_limit: state.mint_rate_limit,
+            mint_rate_window: state.mint_rate_window,
+            max_price_deviation_bps: state.max_price_deviation_bps,
+            min_vintage: state.min_vintage,
+            validity_years: state.validity_years,
+            min_mint_amount: state.min_mint_amount,
+            max_mint_amount: state.max_mint_amount,
+            amount_unit: state.amount_unit,
+            verifier_pubkey: state.verifier_pubkey,
+            ttl_threshold: state.ttl_threshold,
+            ttl_extend_to: state.ttl_extend_to,
+        }
+    }
+
+    /// Summarize the credits an address currently owns: total tons, tons per vintage
+    /// and their value at each credit's latest trade price
+    /// Bought credits count from their purchase, sold amounts leave the seller's credit
+    /// when they fill, and credits cancelled after an upheld flag are left out
+    pub fn get_buyer_portfolio(e: &Env, buyer: Address) -> Portfolio {
+        let credit_ids = index_ids(e, &Index::FarmerCredits(buyer));
+
+        let mut portfolio = Portfolio {
+            total_tons: 0,
+            tons_by_vintage: Map::new(e),
+            market_value: 0,
+        };
+        for credit_id in credit_ids.iter() {
+            let credit = match e.storage().persistent().get::<_, CarbonCredit>(&DataKey::Credit(credit_id.clone())) {
+                Some(credit) if credit.status != CreditStatus::Cancelled => credit,
+                _ => continue,
+            };
+            portfolio.total_tons = checked_add(e, portfolio.total_tons, credit.carbon_amount);
+            let vintage_tons = portfolio.tons_by_vintage.get(credit.vintage).unwrap_or(0);
+            portfolio.tons_by_vintage.set(credit.vintage, checked_add(e, vintage_tons, credit.carbon_amount));
+
+            let history: Vec<TradePoint> = e.storage().persistent()
+                .get(&DataKey::PriceHistory(credit_id))
+                .unwrap_or(Vec::new(e));
+            if let Some(last) = history.last() {
+                portfolio.market_value = checked_add(
+                    e,
+                    portfolio.market_value,
+                    trade_price(e, credit.carbon_amount, last.price_per_ton),
+                );
+            }
+        }
+        portfolio
+    }
+
+    /// Aggregate impact metrics for UN reporting
+    pub fn get_impact_summary(e: &Env) -> ImpactSummary {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let average_price = if state.total_credits_traded > 0 {
+            checked_mul(e, state.total_market_volume, TON) / state.total_credits_traded
+        } else {
+            0
+        };
+        ImpactSummary {
+            total_minted: state.total_credits_minted,
+            total_retired: state.total_credits_retired,
+            total_active: state.total_credits_minted
+                - state.total_credits_retired
+                - state.total_credits_listed,
+            farmer_count: state.farmer_count,
+            total_market_volume: state.total_market_volume,
+            average_price,
+        }
+    }
+
+    /// Supply reconciliation for auditors
+    /// Returns the outstanding supply computed from burned tokens and from reported
+    /// retirements, `(minted - burned, minted - retired)`; the two should always match
+    pub fn reconcile_supply(e: &Env) -> (i128, i128) {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        (
+            state.total_credits_minted - state.total_tokens_burned,
+            state.total_credits_minted - state.total_credits_retired,
+        )
+    }
+
+    /// Token supply in circulation, i.e. the fungible token total supply
+    /// Publishes a `("supply", "drift")` warning when it differs from minted minus retired credits
+    pub fn circulating_credits(e: &Env) -> i128 {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let supply = Base::total_supply(e);
+        let expected = state.total_credits_minted - state.total_credits_retired;
+        if supply != expected {
+            e.events().publish((symbol_short!("supply"), symbol_short!("drift")), (supply, expected));
+        }
+        supply
+    }
+
+    /// Update market settings (admin only)
+    pub fn update_market_settings(
+        e: &Env,
+        market_open: bool,
+        min_verification_level: VerificationLevel,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.market_open = market_open;
+        new_state.min_verification_level = min_verification_level;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        e.events().publish(
+            (symbol_short!("market"), symbol_short!("settings")),
+            (new_state.admin, market_open, min_verification_level),
+        );
+
+        Ok(())
+    }
+
+    /// Accept or stop accepting a verification level for new mints and verifications
+    /// (admin only), letting governance raise standards over time
+    pub fn set_level_accepted(
+        e: &Env,
+        level: VerificationLevel,
+        accepted: bool,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let key = DataKey::LevelDisabled(level);
+        if accepted {
+            e.storage().persistent().remove(&key);
+        } else {
+            e.storage().persistent().set(&key, &true);
+            extend_ttl(e, &state, &key);
+        }
+
+        Ok(())
+    }
+
+    /// Check whether a verification level is accepted for new credits
+    pub fn is_level_accepted(e: &Env, level: VerificationLevel) -> bool {
+        !e.storage().persistent().has(&DataKey::LevelDisabled(level))
+    }
+
+    /// Require buyers to be on the KYC allowlist, or lift the requirement (admin only)
+    pub fn update_buyer_kyc(e: &Env, require_buyer_kyc: bool) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.require_buyer_kyc = require_buyer_kyc;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update how far persistent records are extended when touched (admin only)
+    pub fn update_ttl_settings(
+        e: &Env,
+        ttl_threshold: u32,
+        ttl_extend_to: u32,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if ttl_threshold > ttl_extend_to {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.ttl_threshold = ttl_threshold;
+        new_state.ttl_extend_to = ttl_extend_to;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the protocol fee and the treasury receiving it (admin only)
+    pub fn update_fee_settings(
+        e: &Env,
+        fee_bps: u32,
+        treasury: Address,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if fee_bps > MAX_FEE_BPS {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.fee_bps = fee_bps;
+        new_state.treasury = treasury;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the anti-spam fee held for each new order; 0 disables it (admin only)
+    pub fn update_list_fee(e: &Env, list_fee: i128) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if list_fee < 0 {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.list_fee = list_fee;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the resale royalty stamped on credits minted from now on (admin only)
+    /// Credits keep the royalty they were minted with
+    pub fn update_royalty_settings(e: &Env, royalty_bps: u32) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if royalty_bps > MAX_ROYALTY_BPS {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.royalty_bps = royalty_bps;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the range of carbon amounts accepted for a single credit (admin only)
+    pub fn update_mint_bounds(
+        e: &Env,
+        min_mint_amount: i128,
+        max_mint_amount: i128,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if min_mint_amount <= 0 || min_mint_amount > max_mint_amount {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.min_mint_amount = min_mint_amount;
+        new_state.max_mint_amount = max_mint_amount;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the base units minted and retired amounts must be a multiple of (admin only)
+    /// e.g. `1_000` keeps amounts to whole kilograms, `TON` to whole tons
+    pub fn update_amount_unit(e: &Env, amount_unit: i128) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if amount_unit <= 0 {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.amount_unit = amount_unit;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Withdraw protocol fees held by the contract to `to` (admin only)
+    pub fn withdraw_fees(e: &Env, to: Address, amount: i128) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if amount <= 0 || amount > state.accumulated_fees {
+            panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+        }
+
+        let mut new_state = state;
+        new_state.accumulated_fees -= amount;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        token::Client::new(e, &new_state.payment_token)
+            .transfer(&e.current_contract_address(), &to, &amount);
+
+        e.events().publish((symbol_short!("admin"), symbol_short!("withdraw")), (to, amount));
+
+        Ok(())
+    }
+
+    /// Set or clear the ed25519 key of the external verification oracle (admin only)
+    pub fn update_verifier_pubkey(e: &Env, verifier_pubkey: Option<BytesN<32>>) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.verifier_pubkey = verifier_pubkey;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update how far a trade may stray from a credit's recent average price (admin only)
+    /// e.g. `5_000` rejects trades more than 50% above or below it; 0 disables the check
+    pub fn update_price_deviation(e: &Env, max_price_deviation_bps: u32) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.max_price_deviation_bps = max_price_deviation_bps;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update how fees and royalties round fractional base units (admin only)
+    pub fn update_rounding_mode(e: &Env, rounding_mode: RoundingMode) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.rounding_mode = rounding_mode;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the per-farmer mint rate limit: at most `mint_rate_limit` mints within any
+    /// `mint_rate_window` seconds; a limit of 0 disables it (admin only)
+    pub fn update_mint_rate_limit(
+        e: &Env,
+        mint_rate_limit: u32,
+        mint_rate_window: u64,
+    ) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        if mint_rate_limit > 0 && (mint_rate_window == 0 || mint_rate_limit > MAX_BATCH_SIZE) {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+
+        let mut new_state = state;
+        new_state.mint_rate_limit = mint_rate_limit;
+        new_state.mint_rate_window = mint_rate_window;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update the oldest vintage year accepted for new credits (admin only)
+    pub fn update_min_vintage(e: &Env, min_vintage: u32) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.min_vintage = min_vintage;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Update how many years after its vintage a credit stays tradable (admin only)
+    /// A value of 0 disables expiry
+    pub fn update_validity_years(e: &Env, validity_years: u32) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.validity_years = validity_years;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Propose a new admin (admin only); takes effect once the new admin accepts
+    pub fn propose_admin(e: &Env, new_admin: Address) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        e.storage().instance().set(&DataKey::PendingAdmin, &new_admin);
+
+        e.events().publish(
+            (symbol_short!("admin"), symbol_short!("propose")),
+            (state.admin, new_admin),
+        );
+
+        Ok(())
+    }
+
+    /// Accept a pending admin proposal, completing the handover
+    pub fn accept_admin(e: &Env) -> Result<(), CarbonCreditError> {
+        let pending_admin: Address = e.storage().instance().get(&DataKey::PendingAdmin)
+            .ok_or(CarbonCreditError::NotAuthorized)?;
+        pending_admin.require_auth();
+
+        let mut state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        let previous_admin = state.admin;
+        state.admin = pending_admin.clone();
+        e.storage().instance().set(&DataKey::State, &state);
+        e.storage().instance().remove(&DataKey::PendingAdmin);
+
+        e.events().publish(
+            (symbol_short!("admin"), symbol_short!("accept")),
+            (previous_admin, pending_admin),
+        );
+
+        Ok(())
+    }
+
+    /// Emergency pause for market (admin only)
+    pub fn pause_market(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.market_open = false;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        e.events().publish((symbol_short!("market"), symbol_short!("paused")), new_state.admin);
+
+        Ok(())
+    }
+
+    /// Resume market operations (admin only)
+    pub fn resume_market(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.market_open = true;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        e.events().publish((symbol_short!("market"), symbol_short!("resumed")), new_state.admin);
+
+        Ok(())
+    }
+
+    /// Replace the contract code with an uploaded Wasm (admin only)
+    /// Storage is kept, so a new version must be able to read the current layout
+    pub fn upgrade(e: &Env, new_wasm_hash: BytesN<32>) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.version += 1;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        e.events().publish(
+            (symbol_short!("admin"), symbol_short!("upgrade")),
+            (new_wasm_hash.clone(), new_state.version),
+        );
+        e.deployer().update_current_contract_wasm(new_wasm_hash);
+
+        Ok(())
+    }
+
+    /// Rewrite contract state stored in an earlier layout to the current one (admin only)
+    /// Steps through every layout in between; returns the schema version written.
+    /// `payment_token` is only read when migrating a layout from before trades were
+    /// paid for
+    pub fn migrate(e: &Env, payment_token: Option<Address>) -> Result<u32, CarbonCreditError> {
+        // Work on the raw state, since decoding a map with a different field set
+        // into a struct traps instead of returning an error
+        let mut raw: Map<Symbol, Val> = e.storage().instance().get(&DataKey::State).unwrap();
+        let stored_version = stored_schema_version(e, &raw)?;
+        if stored_version >= SCHEMA_VERSION {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        let admin: Address = state_field(e, &raw, "admin")?;
+        admin.require_auth();
+
+        for version in stored_version..SCHEMA_VERSION {
+            upgrade_state_layout(e, &mut raw, version, &payment_token)?;
+        }
+        let state = ContractState::try_from_val(e, &raw.to_val())
+            .map_err(|_| CarbonCreditError::InvalidCreditData)?;
+        e.storage().instance().set(&DataKey::State, &state);
+
+        e.events().publish(
+            (symbol_short!("admin"), symbol_short!("migrate")),
+            (stored_version, SCHEMA_VERSION),
+        );
+
+        Ok(SCHEMA_VERSION)
+    }
+
+    /// Version of the deployed contract code
+    pub fn version(e: &Env) -> u32 {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.version
+    }
+
+    /// Halt every credit and market operation (admin only)
+    /// Views and admin controls stay available so an incident can be handled
+    pub fn pause_contract(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.contract_paused = true;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Resume credit and market operations (admin only)
+    pub fn unpause_contract(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.contract_paused = false;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Freeze every token movement, including direct transfers, trades and
+    /// retirements, during a security incident (admin only)
+    pub fn freeze_tokens(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.tokens_frozen = true;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+
+    /// Lift the emergency token freeze (admin only)
+    pub fn unfreeze_tokens(e: &Env) -> Result<(), CarbonCreditError> {
+        let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        state.admin.require_auth();
+
+        let mut new_state = state;
+        new_state.tokens_frozen = false;
+        e.storage().instance().set(&DataKey::State, &new_state);
+
+        Ok(())
+    }
+}
+
+#[default_impl]
+#[contractimpl]
+impl FungibleToken for CarbonCreditToken {
+    type ContractType = Base;
+
+    // Raw token transfers honour the same pause and blocklist as the market;
+    // `Base` requires `from` (or `spender`) to authorize the transfer
+    fn transfer(e: &Env, from: Address, to: Address, amount: i128) {
+        require_not_paused(e);
+        require_tokens_unfrozen(e);
+        require_not_blocked(e, &from);
+        require_not_blocked(e, &to);
+        Base::transfer(e, &from, &to, amount);
+        record_token_transfer(e, &from, &to, amount);
+    }
+
+    fn transfer_from(e: &Env, spender: Address, from: Address, to: Address, amount: i128) {
+        require_not_paused(e);
+        require_tokens_unfrozen(e);
+        require_not_blocked(e, &spender);
+        require_not_blocked(e, &from);
+        require_not_blocked(e, &to);
+        Base::transfer_from(e, &spender, &from, &to, amount);
+        record_token_transfer(e, &from, &to, amount);
+    }
+}
+
+/// Validate, store and mint a single carbon credit; the caller checks authorization
+/// `signature` is the oracle attestation, required once a verifier key is configured
+fn mint_credit(e: &Env, request: MintRequest, signature: Option<BytesN<64>>) -> String {
+    require_not_paused(e);
+
+    let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+
+    // Supply diverging from the recorded totals means a bug or corrupted storage;
+    // minting on top of it would compound the error
+    if Base::total_supply(e) != state.total_credits_minted - state.total_credits_retired {
+        panic_with_error!(e, CarbonCreditError::StateInconsistent);
+    }
+
+    if let Some(pubkey) = &state.verifier_pubkey {
+        let signature = signature
+            .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::VerificationFailed));
+        let digest: Bytes = e.crypto().sha256(&request.clone().to_xdr(e)).to_bytes().into();
+        verify_oracle_signature(e, pubkey, &digest, &signature);
+    }
+    let MintRequest {
+        farmer_address,
+        farm_id,
+        season_id,
+        carbon_amount,
+        verification_level,
+        methodology,
+        vintage,
+        report_hash,
+        coordinates,
+        metadata,
+    } = request;
+
+    require_not_blocked(e, &farmer_address);
+
+    // Validate carbon credit data; amounts outside the configured range are dust or
+    // implausible for a smallholder plot
+    if carbon_amount < state.min_mint_amount || carbon_amount > state.max_mint_amount {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    require_aligned_amount(e, &state, carbon_amount);
+    require_level_accepted(e, verification_level);
+    validate_coordinates(e, &coordinates);
+    validate_report_hash(e, &report_hash);
+    if metadata.len() > MAX_METADATA_KEYS {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    if vintage < state.min_vintage || vintage > current_year(e) {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    if !e.storage().persistent().has(&DataKey::Methodology(methodology.clone())) {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    record_mint_rate(e, &state, &farmer_address);
+
+    // Generate unique credit ID; the nonce separates mints sharing a ledger timestamp
+    let nonce = state.credit_nonce + 1;
+    let mut credit_id = IdBuilder::new();
+    credit_id.push_str(e, &farm_id);
+    credit_id.push_bytes(e, b"_");
+    credit_id.push_str(e, &season_id);
+    credit_id.push_bytes(e, b"_");
+    credit_id.push_u64(e, vintage as u64);
+    credit_id.push_bytes(e, b"_");
+    credit_id.push_u64(e, e.ledger().timestamp());
+    credit_id.push_bytes(e, b"_");
+    credit_id.push_u64(e, nonce);
+    let credit_id = credit_id.build(e);
+
+    // Check if credit already exists
+    let credit_key = DataKey::Credit(credit_id.clone());
+    if e.storage().persistent().has(&credit_key) {
+        panic_with_error!(e, CarbonCreditError::CreditAlreadyExists);
+    }
+
+    // Each MRV report may back only one credit, otherwise its sequestration is double counted
+    let report_key = DataKey::ReportHash(report_hash.clone());
+    if e.storage().persistent().has(&report_key) {
+        panic_with_error!(e, CarbonCreditError::CreditAlreadyExists);
+    }
+    e.storage().persistent().set(&report_key, &credit_id);
+    extend_ttl(e, &state, &report_key);
+
+    // A farm yields at most one credit per season; a second mint is a resubmission
+    // or fraud unless the admin allowed an amendment
+    let season_key = DataKey::FarmSeason(farm_id.clone(), season_id.clone());
+    if e.storage().persistent().has(&season_key) {
+        let amendment_key = DataKey::AmendmentAllowed(farm_id.clone(), season_id.clone());
+        if !e.storage().persistent().has(&amendment_key) {
+            panic_with_error!(e, CarbonCreditError::CreditAlreadyExists);
+        }
+        e.storage().persistent().remove(&amendment_key);
+    }
+    e.storage().persistent().set(&season_key, &credit_id);
+    extend_ttl(e, &state, &season_key);
+
+    // Create carbon credit
+    let credit = CarbonCredit {
+        id: credit_id.clone(),
+        farmer_address: farmer_address.clone(),
+        farm_id,
+        season_id,
+        carbon_amount,
+        verification_level,
+        methodology,
+        vintage,
+        report_hash,
+        created_at: e.ledger().timestamp(),
+        status: CreditStatus::Pending,
+        coordinates,
+        metadata,
+        original_farmer: farmer_address.clone(),
+        royalty_bps: state.royalty_bps,
+    };
+
+    // Store the credit
+    e.storage().persistent().set(&credit_key, &credit);
+    extend_ttl(e, &state, &credit_key);
+
+    // Index the credit under its farmer, vintage and methodology
+    index_push(e, &state, &Index::FarmerCredits(farmer_address.clone()), &credit_id);
+    index_catalog(e, &state, &credit);
+    index_push(e, &state, &Index::AllCredits, &credit_id);
+    record_owner(e, &state, &credit_id, &farmer_address);
+
+    add_region_total(e, &state, &credit.coordinates, carbon_amount);
+    add_vintage_stats(e, &state, vintage, carbon_amount, 0);
+    update_leaderboard(e, &state, &farmer_address, carbon_amount);
+    record_provenance(e, &state, &credit_id, symbol_short!("mint"), &farmer_address, carbon_amount);
+
+    // Update contract state, counting each farmer once
+    let mut new_state = state;
+    new_state.credit_nonce = nonce;
+    new_state.total_credits_minted = checked_add(e, new_state.total_credits_minted, carbon_amount);
+    let farmer_key = DataKey::Farmer(farmer_address.clone());
+    if !e.storage().persistent().has(&farmer_key) {
+        e.storage().persistent().set(&farmer_key, &true);
+        new_state.farmer_count += 1;
+    }
+    extend_ttl(e, &new_state, &farmer_key);
+    e.storage().instance().set(&DataKey::State, &new_state);
+
+    // Mint tokens to farmer (1 token = 1 ton CO2e)
+    Base::mint(e, &farmer_address, carbon_amount);
+
+    e.events().publish(
+        (symbol_short!("credit"), symbol_short!("mint")),
+        (farmer_address, carbon_amount),
+    );
+
+    credit_id
+}
+
+/// Layout version of a raw contract state; 0 for states written before versioning
+fn stored_schema_version(e: &Env, raw: &Map<Symbol, Val>) -> Result<u32, CarbonCreditError> {
+    if raw.contains_key(Symbol::new(e, "schema_version")) {
+        state_field(e, raw, "schema_version")
+    } else {
+        Ok(0)
+    }
+}
+
+/// Read and decode one field of a raw contract state
+fn state_field<T: TryFromVal<Env, Val>>(e: &Env, raw: &Map<Symbol, Val>, name: &str) -> Result<T, CarbonCreditError> {
+    raw.get(Symbol::new(e, name))
+        .and_then(|value| T::try_from_val(e, &value).ok())
+        .ok_or(CarbonCreditError::InvalidCreditData)
+}
+
+/// Upgrade a raw contract state from layout `version` to `version + 1`
+/// Fields a layout introduced start at the values a new deployment starts with
+fn upgrade_state_layout(
+    e: &Env,
+    raw: &mut Map<Symbol, Val>,
+    version: u32,
+    payment_token: &Option<Address>,
+) -> Result<(), CarbonCreditError> {
+    let key = |name: &str| Symbol::new(e, name);
+    match version {
+        // Unversioned layouts, from the original one up to the versioned layout
+        0 => {
+            // The original layout named the minimum verification level
+            if let Ok(level_name) = state_field::<String>(e, raw, "min_verification_level") {
+                let level = VerificationLevel::from_name(e, &level_name)
+                    .ok_or(CarbonCreditError::InvalidCreditData)?;
+                raw.set(key("min_verification_level"), level.into_val(e));
+            }
+            if !raw.contains_key(key("payment_token")) {
+                let payment_token = payment_token.clone().ok_or(CarbonCreditError::InvalidCreditData)?;
+                raw.set(key("payment_token"), payment_token.into_val(e));
+            }
+            let admin: Address = state_field(e, raw, "admin")?;
+            // Retiring has always burned the retired tokens
+            let retired: i128 = state_field(e, raw, "total_credits_retired")?;
+            for (name, value) in [
+                ("ttl_threshold", (30 * DAY_IN_LEDGERS).into_val(e)),
+                ("ttl_extend_to", (180 * DAY_IN_LEDGERS).into_val(e)),
+                ("min_vintage", 2000u32.into_val(e)),
+                ("fee_bps", 0u32.into_val(e)),
+                ("treasury", admin.into_val(e)),
+                ("validity_years", 0u32.into_val(e)),
+                ("total_tokens_burned", retired.into_val(e)),
+                ("require_buyer_kyc", false.into_val(e)),
+                ("total_credits_traded", 0i128.into_val(e)),
+                ("total_credits_listed", 0i128.into_val(e)),
+                ("royalty_bps", 0u32.into_val(e)),
+                ("contract_paused", false.into_val(e)),
+                ("version", 1u32.into_val(e)),
+            ] {
+                if !raw.contains_key(key(name)) {
+                    raw.set(key(name), value);
+                }
+            }
+        }
+        // Layout 1 is the last unversioned one; layout 2 only adds `schema_version`
+        1 => {}
+        2 => {
+            raw.set(key("min_mint_amount"), TON.into_val(e));
+            raw.set(key("max_mint_amount"), (10_000 * TON).into_val(e));
+        }
+        3 => raw.set(key("amount_unit"), 1i128.into_val(e)),
+        4 => raw.set(key("max_price_deviation_bps"), 0u32.into_val(e)),
+        5 => raw.set(key("verifier_pubkey"), Option::<BytesN<32>>::None.into_val(e)),
+        6 => raw.set(key("accumulated_fees"), 0i128.into_val(e)),
+        7 => raw.set(key("rounding_mode"), RoundingMode::Floor.into_val(e)),
+        8 => raw.set(key("tokens_frozen"), false.into_val(e)),
+        9 => raw.set(key("credit_nonce"), 0u64.into_val(e)),
+        10 => raw.set(key("list_fee"), 0i128.into_val(e)),
+        11 => {
+            raw.set(key("mint_rate_limit"), 0u32.into_val(e));
+            raw.set(key("mint_rate_window"), 0u64.into_val(e));
+        }
+        _ => return Err(CarbonCreditError::InvalidCreditData),
+    }
+    raw.set(key("schema_version"), (version + 1).into_val(e));
+    Ok(())
+}
+
+/// Verify an oracle ed25519 signature with the host
+/// The host traps on a signature that does not match, so malformed keys and
+/// signatures are rejected up front with `VerificationFailed`
+fn verify_oracle_signature(e: &Env, pubkey: &BytesN<32>, message: &Bytes, signature: &BytesN<64>) {
+    if pubkey.len() != 32 || signature.len() != 64 {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+    e.crypto().ed25519_verify(pubkey, message, signature);
+}
+
+/// Require a (latitude, longitude) pair within valid degree bounds
+fn validate_coordinates(e: &Env, coordinates: &Vec<i128>) {
+    if coordinates.len() != 2 {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    let latitude = coordinates.get_unchecked(0);
+    let longitude = coordinates.get_unchecked(1);
+    if !(-90 * COORDINATE_SCALE..=90 * COORDINATE_SCALE).contains(&latitude)
+        || !(-180 * COORDINATE_SCALE..=180 * COORDINATE_SCALE).contains(&longitude)
+    {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+}
+
+/// Key of the one-degree grid cell containing a (latitude, longitude) pair, e.g. `-12_45`
+/// Cells are floored so every point maps to exactly one of at most 180 * 360 regions
+fn region_key(e: &Env, coordinates: &Vec<i128>) -> String {
+    let latitude = coordinates.get_unchecked(0).div_euclid(COORDINATE_SCALE);
+    let longitude = coordinates.get_unchecked(1).div_euclid(COORDINATE_SCALE);
+    let mut key = IdBuilder::new();
+    key.push_i64(e, latitude as i64);
+    key.push_bytes(e, b"_");
+    key.push_i64(e, longitude as i64);
+    key.build(e)
+}
+
+/// Append the owner a credit record just passed to, with the current time
+fn record_owner(e: &Env, state: &ContractState, credit_id: &String, owner: &Address) {
+    let key = DataKey::CreditOwners(credit_id.clone());
+    let mut owners: Vec<(Address, u64)> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+    owners.push_back((owner.clone(), e.ledger().timestamp()));
+    e.storage().persistent().set(&key, &owners);
+    extend_ttl(e, state, &key);
+}
+
+/// Append a step to a credit's lifecycle history
+/// Entries are stored one per key so the history never outgrows a single ledger entry
+fn record_provenance(e: &Env, state: &ContractState, credit_id: &String, action: Symbol, actor: &Address, amount: i128) {
+    let count_key = DataKey::ProvenanceCount(credit_id.clone());
+    let count: u32 = e.storage().persistent().get(&count_key).unwrap_or(0);
+    let entry_key = DataKey::Provenance(credit_id.clone(), count);
+    e.storage().persistent().set(&entry_key, &ProvenanceEntry {
+        action,
+        actor: actor.clone(),
+        amount,
+        timestamp: e.ledger().timestamp(),
+    });
+    extend_ttl(e, state, &entry_key);
+    e.storage().persistent().set(&count_key, &(count + 1));
+    extend_ttl(e, state, &count_key);
+}
+
+/// Log a raw token transfer in the provenance of the sender's credits
+/// Tokens are fungible, so the amount is charged to the sender's live credits in
+/// index order until it is covered; the recipient is recorded as the actor
+fn record_token_transfer(e: &Env, from: &Address, to: &Address, amount: i128) {
+    let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+    let credit_ids = index_ids(e, &Index::FarmerCredits(from.clone()));
+    let mut remaining = amount;
+    for credit_id in credit_ids.iter() {
+        if remaining <= 0 {
+            break;
+        }
+        let Some(credit) = e.storage().persistent().get::<_, CarbonCredit>(&DataKey::Credit(credit_id.clone())) else {
+            continue;
+        };
+        if credit.status != CreditStatus::Pending && credit.status != CreditStatus::Verified {
+            continue;
+        }
+        let share = remaining.min(credit.carbon_amount);
+        if share > 0 {
+            record_provenance(e, &state, &credit_id, symbol_short!("tok_xfer"), to, share);
+            remaining -= share;
+        }
+    }
+}
+
+/// Add minted tons to a farmer's lifetime total and re-rank them on the leaderboard
+/// Totals only grow, so a farmer outside the tracked top enters it with their full total
+fn update_leaderboard(e: &Env, state: &ContractState, farmer: &Address, minted: i128) {
+    let minted_key = DataKey::FarmerMinted(farmer.clone());
+    let total = checked_add(e, e.storage().persistent().get(&minted_key).unwrap_or(0), minted);
+    e.storage().persistent().set(&minted_key, &total);
+    extend_ttl(e, state, &minted_key);
+
+    let mut top: Vec<(Address, i128)> = e.storage().persistent().get(&DataKey::TopFarmers).unwrap_or(Vec::new(e));
+    if let Some(i) = top.iter().position(|(address, _)| address == *farmer) {
+        top.remove(i as u32);
+    }
+    let rank = top.iter().position(|(_, amount)| amount < total).unwrap_or(top.len() as usize) as u32;
+    if rank < LEADERBOARD_SIZE {
+        top.insert(rank, (farmer.clone(), total));
+        if top.len() > LEADERBOARD_SIZE {
+            top.pop_back();
+        }
+        e.storage().persistent().set(&DataKey::TopFarmers, &top);
+        extend_ttl(e, state, &DataKey::TopFarmers);
+    }
+}
+
+/// Add tons to the live verified total of a verification level; negative to remove them
+fn add_level_amount(e: &Env, state: &ContractState, level: VerificationLevel, amount: i128) {
+    let key = DataKey::LevelAmount(level);
+    let total: i128 = e.storage().persistent().get(&key).unwrap_or(0);
+    e.storage().persistent().set(&key, &checked_add(e, total, amount));
+    extend_ttl(e, state, &key);
+}
+
+/// Add minted and retired tons to a vintage year's totals
+fn add_vintage_stats(e: &Env, state: &ContractState, vintage: u32, minted: i128, retired: i128) {
+    let key = DataKey::VintageStats(vintage);
+    let (total_minted, total_retired): (i128, i128) = e.storage().persistent().get(&key).unwrap_or((0, 0));
+    e.storage().persistent().set(&key, &(checked_add(e, total_minted, minted), checked_add(e, total_retired, retired)));
+    extend_ttl(e, state, &key);
+}
+
+/// Add minted tons to the total of the credit's grid cell
+fn add_region_total(e: &Env, state: &ContractState, coordinates: &Vec<i128>, amount: i128) {
+    let region = region_key(e, coordinates);
+    let total_key = DataKey::RegionTotal(region.clone());
+    let total: Option<i128> = e.storage().persistent().get(&total_key);
+    if total.is_none() {
+        let mut regions: Vec<String> = e.storage().persistent().get(&DataKey::Regions).unwrap_or(Vec::new(e));
+        regions.push_back(region);
+        e.storage().persistent().set(&DataKey::Regions, &regions);
+        extend_ttl(e, state, &DataKey::Regions);
+    }
+    e.storage().persistent().set(&total_key, &checked_add(e, total.unwrap_or(0), amount));
+    extend_ttl(e, state, &total_key);
+}
+
+/// Require the MRV report hash to look like an IPFS CID so the evidence can be retrieved:
+/// a base58 CIDv0 (`Qm`, 46 characters) or a base32 CIDv1 (`bafy`/`bafk`)
+fn validate_report_hash(e: &Env, report_hash: &String) {
+    let len = report_hash.len() as usize;
+    if len > MAX_CID_LEN {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    let mut buf = [0u8; MAX_CID_LEN];
+    let hash = &mut buf[..len];
+    report_hash.copy_into_slice(hash);
+
+    let valid = if hash.starts_with(b"Qm") {
+        len == CIDV0_LEN
+            && hash.iter().all(|c| c.is_ascii_alphanumeric() && !matches!(c, b'0' | b'O' | b'I' | b'l'))
+    } else if hash.starts_with(b"bafy") || hash.starts_with(b"bafk") {
+        len >= MIN_CIDV1_LEN
+            && hash.iter().all(|c| c.is_ascii_lowercase() || (b'2'..=b'7').contains(c))
+    } else {
+        false
+    };
+    if !valid {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+}
+
+/// Calendar year of the current ledger close time (UTC)
+fn current_year(e: &Env) -> u32 {
+    year_of(e.ledger().timestamp())
+}
+
+/// Calendar year of a ledger timestamp
+fn year_of(timestamp: u64) -> u32 {
+    // Civil-from-days conversion (proleptic Gregorian calendar)
+    let days = timestamp / 86_400;
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let year = yoe + era * 400 + if mp >= 10 { 1 } else { 0 };
+    year as u32
+}
+
+/// Extend the TTL of a persistent record, and of the contract instance it
+/// depends on, using the thresholds in state
+fn extend_ttl<K: IntoVal<Env, Val>>(e: &Env, state: &ContractState, key: &K) {
+    e.storage().persistent().extend_ttl(key, state.ttl_threshold, state.ttl_extend_to);
+    e.storage().instance().extend_ttl(state.ttl_threshold, state.ttl_extend_to);
+}
+
+/// Number of ids in an index
+fn index_len(e: &Env, index: &Index) -> u32 {
+    e.storage().persistent().get(&DataKey::IndexLen(index.clone())).unwrap_or(0)
+}
+
+/// Append an id to an index; ids already in it are left where they are
+fn index_push(e: &Env, state: &ContractState, index: &Index, id: &String) {
+    let position_key = DataKey::IndexPosition(index.clone(), id.clone());
+    if e.storage().persistent().has(&position_key) {
+        return;
+    }
+    let len = index_len(e, index);
+    let entry_key = DataKey::IndexEntry(index.clone(), len);
+    e.storage().persistent().set(&entry_key, id);
+    extend_ttl(e, state, &entry_key);
+    e.storage().persistent().set(&position_key, &len);
+    extend_ttl(e, state, &position_key);
+    let len_key = DataKey::IndexLen(index.clone());
+    e.storage().persistent().set(&len_key, &(len + 1));
+    extend_ttl(e, state, &len_key);
+}
+
+/// Remove an id from an index, moving the index's last id into its place
+fn index_remove(e: &Env, index: &Index, id: &String) {
+    let position_key = DataKey::IndexPosition(index.clone(), id.clone());
+    let Some(position) = e.storage().persistent().get::<_, u32>(&position_key) else {
+        return;
+    };
+    let last = index_len(e, index) - 1;
+    let last_key = DataKey::IndexEntry(index.clone(), last);
+    if position != last {
+        let last_id: String = e.storage().persistent().get(&last_key).unwrap();
+        e.storage().persistent().set(&DataKey::IndexEntry(index.clone(), position), &last_id);
+        e.storage().persistent().set(&DataKey::IndexPosition(index.clone(), last_id), &position);
+    }
+    e.storage().persistent().remove(&last_key);
+    e.storage().persistent().remove(&position_key);
+    e.storage().persistent().set(&DataKey::IndexLen(index.clone()), &last);
+}
+
+/// Ids of an index from position `start`, `limit` of them and at most `MAX_PAGE_SIZE`
+fn index_page(e: &Env, index: &Index, start: u32, limit: u32) -> Vec<String> {
+    let end = index_len(e, index).min(start.saturating_add(limit.min(MAX_PAGE_SIZE)));
+    let mut ids = Vec::new(e);
+    for i in start..end {
+        if let Some(id) = e.storage().persistent().get(&DataKey::IndexEntry(index.clone(), i)) {
+            ids.push_back(id);
+        }
+    }
+    ids
+}
+
+/// Every id of an index, for views that aggregate over all of it
+fn index_ids(e: &Env, index: &Index) -> Vec<String> {
+    let mut ids = Vec::new(e);
+    for i in 0..index_len(e, index) {
+        if let Some(id) = e.storage().persistent().get(&DataKey::IndexEntry(index.clone(), i)) {
+            ids.push_back(id);
+        }
+    }
+    ids
+}
+
+/// Reject verification levels governance no longer accepts for new credits
+fn require_level_accepted(e: &Env, level: VerificationLevel) {
+    if e.storage().persistent().has(&DataKey::LevelDisabled(level)) {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+}
+
+/// Reject credits verified below the market's minimum level
+fn require_min_level(e: &Env, state: &ContractState, credit: &CarbonCredit) {
+    if credit.verification_level.rank() < state.min_verification_level.rank() {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+}
+
+/// Add a credit to the vintage and methodology indexes buyers filter by,
+/// and to the verifiers' queue while it is pending
+fn index_catalog(e: &Env, state: &ContractState, credit: &CarbonCredit) {
+    index_push(e, state, &Index::VintageCredits(credit.vintage), &credit.id);
+    index_push(e, state, &Index::MethodologyCredits(credit.methodology.clone()), &credit.id);
+    if credit.status == CreditStatus::Pending {
+        index_push(e, state, &Index::PendingCredits, &credit.id);
+    }
+}
+
+/// Remove a credit that is no longer live from the vintage and methodology indexes
+/// and the verifiers' queue
+fn unindex_catalog(e: &Env, credit: &CarbonCredit) {
+    index_remove(e, &Index::VintageCredits(credit.vintage), &credit.id);
+    index_remove(e, &Index::MethodologyCredits(credit.methodology.clone()), &credit.id);
+    index_remove(e, &Index::PendingCredits, &credit.id);
+}
+
+/// Load a page of the credits listed in an index
+fn credit_page(e: &Env, index: &Index, start: u32, limit: u32) -> Vec<CarbonCredit> {
+    let mut credits = Vec::new(e);
+    for credit_id in index_page(e, index, start, limit).iter() {
+        if let Some(credit) = e.storage().persistent().get(&DataKey::Credit(credit_id)) {
+            credits.push_back(credit);
+        }
+    }
+    credits
+}
+
+/// Reject state changes while the admin has paused the contract
+fn require_not_paused(e: &Env) {
+    let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+    if state.contract_paused {
+        panic_with_error!(e, CarbonCreditError::ContractPaused);
+    }
+}
+
+/// Reject token movements while the admin has frozen tokens
+fn require_tokens_unfrozen(e: &Env) {
+    let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+    if state.tokens_frozen {
+        panic_with_error!(e, CarbonCreditError::TokensFrozen);
+    }
+}
+
+/// Count a mint against the farmer's rolling window, rejecting it once the limit is reached
+/// Caps the damage a compromised verifier or oracle key can do
+fn record_mint_rate(e: &Env, state: &ContractState, farmer: &Address) {
+    if state.mint_rate_limit == 0 {
+        return;
+    }
+    let now = e.ledger().timestamp();
+    let key = DataKey::FarmerMints(farmer.clone());
+    let mints: Vec<u64> = e.storage().persistent().get(&key).unwrap_or(Vec::new(e));
+
+    let mut recent = Vec::new(e);
+    for minted_at in mints.iter() {
+        if now.saturating_sub(minted_at) < state.mint_rate_window {
+            recent.push_back(minted_at);
+        }
+    }
+    if recent.len() >= state.mint_rate_limit {
+        panic_with_error!(e, CarbonCreditError::RateLimited);
+    }
+    recent.push_back(now);
+    e.storage().persistent().set(&key, &recent);
+    extend_ttl(e, state, &key);
+}
+
+/// Reject addresses frozen by the admin
+fn require_not_blocked(e: &Env, address: &Address) {
+    if e.storage().persistent().has(&DataKey::Blocked(address.clone())) {
+        panic_with_error!(e, CarbonCreditError::NotAuthorized);
+    }
+}
+
+/// Reject credits frozen while under investigation
+fn require_not_frozen(e: &Env, credit_id: &String) {
+    if e.storage().persistent().has(&DataKey::FrozenCredit(credit_id.clone())) {
+        panic_with_error!(e, CarbonCreditError::NotAuthorized);
+    }
+}
+
+/// Reject amounts below the configured precision, keeping on-chain amounts aligned
+/// with the tons reported by the MRV system
+fn require_aligned_amount(e: &Env, state: &ContractState, amount: i128) {
+    if amount % state.amount_unit != 0 {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+}
+
+/// Time before which a credit may not be listed or transferred; 0 if never locked
+fn unlock_at(e: &Env, credit_id: &String) -> u64 {
+    e.storage().persistent().get(&DataKey::CreditUnlockAt(credit_id.clone())).unwrap_or(0)
+}
+
+/// Lock a credit until `unlock_at`; times already passed leave it unlocked
+fn set_unlock_at(e: &Env, state: &ContractState, credit_id: &String, unlock_at: u64) {
+    if unlock_at <= e.ledger().timestamp() {
+        return;
+    }
+    let key = DataKey::CreditUnlockAt(credit_id.clone());
+    e.storage().persistent().set(&key, &unlock_at);
+    extend_ttl(e, state, &key);
+}
+
+/// Reject credits still within their lock period
+fn require_unlocked(e: &Env, credit_id: &String) {
+    if e.ledger().timestamp() < unlock_at(e, credit_id) {
+        panic_with_error!(e, CarbonCreditError::CreditLocked);
+    }
+}
+
+/// Store a pending credit as verified at `level` and publish the verification
+fn mark_verified(e: &Env, state: &ContractState, verifier: &Address, credit: &mut CarbonCredit, level: VerificationLevel) {
+    credit.status = CreditStatus::Verified;
+    credit.verification_level = level;
+    add_level_amount(e, state, level, credit.carbon_amount);
+    let credit_key = DataKey::Credit(credit.id.clone());
+    e.storage().persistent().set(&credit_key, credit);
+    extend_ttl(e, state, &credit_key);
+    index_remove(e, &Index::PendingCredits, &credit.id);
+    record_provenance(e, state, &credit.id, symbol_short!("verify"), verifier, credit.carbon_amount);
+
+    e.events().publish(
+        (symbol_short!("credit"), symbol_short!("verify")),
+        (credit.id.clone(), verifier.clone(), level),
+    );
+}
+
+/// Reject credits in a terminal status: retired, cancelled or merged into another credit
+fn assert_credit_mutable(e: &Env, credit: &CarbonCredit) {
+    if credit.status == CreditStatus::Retired
+        || credit.status == CreditStatus::Cancelled
+        || credit.status == CreditStatus::Merged
+    {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+}
+
+/// Reject credits with an unresolved flag
+fn require_not_flagged(e: &Env, credit_id: &String) {
+    if e.storage().persistent().has(&DataKey::FlaggedCredit(credit_id.clone())) {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+}
+
+/// Authorize `owner`, or `member` acting for `owner` as a cooperative group
+fn require_owner_auth(e: &Env, owner: &Address, member: &Option<Address>) {
+    match member {
+        None => owner.require_auth(),
+        Some(member) => {
+            member.require_auth();
+            if !is_group_member(e, owner, member) {
+                panic_with_error!(e, CarbonCreditError::NotAuthorized);
+            }
+        }
+    }
+}
+
+/// Whether `member` may act for the cooperative group `group`
+fn is_group_member(e: &Env, group: &Address, member: &Address) -> bool {
+    let members: Vec<Address> = e.storage().persistent()
+        .get(&DataKey::GroupMembers(group.clone()))
+        .unwrap_or(Vec::new(e));
+    members.contains(member)
+}
+
+/// Authorize `manager` as the admin or a registered verifier
+fn require_admin_or_verifier(e: &Env, state: &ContractState, manager: &Address) {
+    manager.require_auth();
+    if *manager != state.admin && !e.storage().persistent().has(&DataKey::Verifier(manager.clone())) {
+        panic_with_error!(e, CarbonCreditError::NotAuthorized);
+    }
+}
+
+/// Whether a credit is past the validity period configured in the contract state
+fn credit_expired(e: &Env, state: &ContractState, credit: &CarbonCredit) -> bool {
+    if state.validity_years == 0 {
+        return false;
+    }
+    let mut valid_from = credit.vintage;
+    if let Some(verified_at) = e.storage().persistent().get::<_, u64>(&DataKey::VerifiedAt(credit.id.clone())) {
+        valid_from = valid_from.max(year_of(verified_at));
+    }
+    current_year(e) > valid_from.saturating_add(state.validity_years)
+}
+
+/// Validate a listing, escrow the seller's credits and store a new active order
+fn create_sell_order(
+    e: &Env,
+    credit_id: String,
+    price_per_ton: i128,
+    list_amount: i128,
+    order_type: &str,
+    member: Option<Address>,
+) -> String {
+    require_not_paused(e);
+
+    let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id.clone()))
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+    assert_credit_mutable(e, &credit);
+
+    // Verify caller owns the credit, or is a member acting for the owning group
+    require_owner_auth(e, &credit.farmer_address, &member);
+    require_not_blocked(e, &credit.farmer_address);
+    require_not_frozen(e, &credit_id);
+    require_not_flagged(e, &credit_id);
+    require_unlocked(e, &credit_id);
+
+    if price_per_ton <= 0 {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+
+    // Only verified credits can be listed
+    if credit.status != CreditStatus::Verified {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+
+    // Credits below the market's minimum verification level cannot be listed
+    let state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+    require_min_level(e, &state, &credit);
+    if credit_expired(e, &state, &credit) {
+        panic_with_error!(e, CarbonCreditError::CreditExpired);
+    }
+
+    // Orders of a credit may together reserve at most what is left of it; sold amounts
+    // have already been carved off into the buyers' credits
+    if list_amount <= 0 {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    let reserved = reserved_amount(e, &credit_id);
+    if checked_add(e, reserved, list_amount) > credit.carbon_amount {
+        panic_with_error!(e, CarbonCreditError::OverListed);
+    }
+
+    // Escrow the listed credits in the contract until they are bought
+    move_credits(e, &credit.farmer_address, &e.current_contract_address(), list_amount);
+    set_reserved_amount(e, &state, &credit_id, reserved + list_amount);
+
+    // Create market order, suffixing a counter when the credit was already listed in this ledger
+    let mut base_id = IdBuilder::new();
+    base_id.push_bytes(e, b"ORDER_");
+    base_id.push_str(e, &credit_id);
+    base_id.push_bytes(e, b"_");
+    base_id.push_u64(e, e.ledger().timestamp());
+    let base_id = base_id.build(e);
+    let mut order_id = base_id.clone();
+    let mut n: u64 = 1;
+    while e.storage().persistent().has(&DataKey::Order(order_id.clone())) {
+        let mut id = IdBuilder::new();
+        id.push_str(e, &base_id);
+        id.push_bytes(e, b"_");
+        id.push_u64(e, n);
+        order_id = id.build(e);
+        n += 1;
+    }
+    let order = MarketOrder {
+        id: order_id.clone(),
+        credit_id: credit_id.clone(),
+        seller_address: credit.farmer_address,
+        order_type: String::from_str(e, order_type),
+        amount: list_amount,
+        price_per_ton,
+        status: String::from_str(e, "Active"),
+        timestamp: e.ledger().timestamp(),
+    };
+
+    // Store order
+    let order_key = DataKey::Order(order_id.clone());
+    e.storage().persistent().set(&order_key, &order);
+    extend_ttl(e, &state, &order_key);
+    index_push(e, &state, &Index::CreditOrders(credit_id.clone()), &order_id);
+    index_push(e, &state, &Index::ActiveOrders, &order_id);
+    index_push(e, &state, &Index::SellerOrders(order.seller_address.clone()), &order_id);
+    index_push(e, &state, &Index::AllOrders, &order_id);
+    record_provenance(e, &state, &credit_id, symbol_short!("list"), &order.seller_address, list_amount);
+
+    // Hold the listing fee from whoever signed the listing until the order fills or is cancelled
+    if state.list_fee > 0 {
+        let payer = member.unwrap_or(order.seller_address.clone());
+        token::Client::new(e, &state.payment_token)
+            .transfer(&payer, &e.current_contract_address(), &state.list_fee);
+        let fee_key = DataKey::ListingFee(order_id.clone());
+        e.storage().persistent().set(&fee_key, &(payer, state.list_fee, list_amount));
+        extend_ttl(e, &state, &fee_key);
+    }
+
+    let mut new_state = state;
+    new_state.total_credits_listed = checked_add(e, new_state.total_credits_listed, order.amount);
+    e.storage().instance().set(&DataKey::State, &new_state);
+
+    order_id
+}
+
+/// First unused credit id of the form `{base}{tag}{n}`, counting n from 1
+fn derive_credit_id(e: &Env, base: &String, tag: &[u8]) -> String {
+    let mut n: u64 = 1;
+    loop {
+        let mut id = IdBuilder::new();
+        id.push_str(e, base);
+        id.push_bytes(e, tag);
+        id.push_u64(e, n);
+        let id = id.build(e);
+        if !e.storage().persistent().has(&DataKey::Credit(id.clone())) {
+            return id;
+        }
+        n += 1;
+    }
+}
+
+/// Validate a purchase against an order and take `amount` off it
+/// Returns the updated order and the price per ton the buyer pays
+fn fill_order(
+    e: &Env,
+    state: &ContractState,
+    order_id: &String,
+    buyer_address: &Address,
+    amount: i128,
+) -> (MarketOrder, i128) {
+    require_not_paused(e);
+
+    if !state.market_open {
+        panic_with_error!(e, CarbonCreditError::MarketNotOpen);
+    }
+
+    // When KYC is required only allowlisted buyers may trade
+    if state.require_buyer_kyc
+        && !e.storage().persistent().has(&DataKey::Allowlisted(buyer_address.clone()))
+    {
+        panic_with_error!(e, CarbonCreditError::NotAuthorized);
+    }
+
+    // Sellers may restrict an order to buyers holding a role
+    let restriction: Option<Symbol> = e.storage().persistent().get(&DataKey::BuyerRestriction(order_id.clone()));
+    if let Some(role) = restriction {
+        if !e.storage().persistent().has(&DataKey::BuyerRole(buyer_address.clone(), role)) {
+            panic_with_error!(e, CarbonCreditError::NotAuthorized);
+        }
+    }
+
+    let mut order: MarketOrder = e.storage().persistent().get(&DataKey::Order(order_id.clone()))
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+
+    if order.status == String::from_str(e, "Filled") {
+        panic_with_error!(e, CarbonCreditError::OrderDepleted);
+    }
+
+    if order.status != String::from_str(e, "Active") {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+
+    if amount <= 0 {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+
+    if amount > order.amount {
+        panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+    }
+
+    // Wash trades would inflate reported market volume
+    if *buyer_address == order.seller_address {
+        panic_with_error!(e, CarbonCreditError::SelfTradeNotAllowed);
+    }
+
+    require_not_blocked(e, buyer_address);
+    require_not_blocked(e, &order.seller_address);
+    require_not_frozen(e, &order.credit_id);
+    require_not_flagged(e, &order.credit_id);
+
+    // Only credits meeting the market's minimum verification level may trade
+    let credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(order.credit_id.clone()))
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+    if credit.status == CreditStatus::Cancelled || credit.status == CreditStatus::Retired {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+    require_min_level(e, state, &credit);
+    if credit_expired(e, state, &credit) {
+        panic_with_error!(e, CarbonCreditError::CreditExpired);
+    }
+
+    // Price follows the decay curve for auction orders
+    let price_per_ton = order_price(e, &order);
+    if order_asset(e, state, order_id) == state.payment_token {
+        require_price_in_bounds(e, state, &order.credit_id, price_per_ton);
+    }
+
+    // Record the fill so the order cannot be drained twice
+    order.amount -= amount;
+    let reserved = reserved_amount(e, &order.credit_id);
+    set_reserved_amount(e, state, &order.credit_id, reserved - amount);
+    if order.amount == 0 {
+        order.status = String::from_str(e, "Filled");
+        index_remove(e, &Index::CreditOrders(order.credit_id.clone()), order_id);
+        index_remove(e, &Index::ActiveOrders, order_id);
+        index_remove(e, &Index::SellerOrders(order.seller_address.clone()), order_id);
+    }
+    let order_key = DataKey::Order(order_id.clone());
+    e.storage().persistent().set(&order_key, &order);
+    extend_ttl(e, state, &order_key);
+
+    (order, price_per_ton)
+}
+
+/// Protocol fee and resale royalty taken out of a trade's total price
+/// Royalties are only due when the seller is not the original farmer
+fn trade_cuts(e: &Env, state: &ContractState, order: &MarketOrder, credit: &CarbonCredit, total_price: i128) -> (i128, i128) {
+    let fee = apply_bps(e, total_price, state.fee_bps, state.rounding_mode);
+    let mut royalty = 0;
+    if order.seller_address != credit.original_farmer {
+        royalty = apply_bps(e, total_price, credit.royalty_bps, state.rounding_mode);
+    }
+    (fee, royalty)
+}
+
+/// Collect the payment from `payer`, pay the treasury, original farmer and seller,
+/// release the escrowed credits to the buyer and record the trade
+/// The bought amount is carved off the seller's credit into a new credit owned by the
+/// buyer, so credit records keep matching who holds the tokens
+fn settle_trade(
+    e: &Env,
+    state: ContractState,
+    order: &MarketOrder,
+    payer: &Address,
+    buyer_address: &Address,
+    amount: i128,
+    price_per_ton: i128,
+) {
+    let total_price = trade_price(e, amount, price_per_ton);
+
+    // Earnings, price history and volume are denominated in the default payment token,
+    // so trades settled in other assets are left out of them
+    let asset = order_asset(e, &state, &order.id);
+    let in_payment_token = asset == state.payment_token;
+
+    // Protocol fee goes to the treasury, resale royalties to the original farmer
+    // and the rest to the seller
+    let credit_key = DataKey::Credit(order.credit_id.clone());
+    let mut credit: CarbonCredit = e.storage().persistent().get(&credit_key)
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+    let (fee, royalty) = trade_cuts(e, &state, order, &credit, total_price);
+
+    // Settle in a fixed order: collect the whole payment into the contract, pay it out,
+    // then release the credits. Any failing step, such as a buyer short of funds, reverts
+    // the whole invocation, so credits never move without payment
+    let payment = token::Client::new(e, &asset);
+    let contract = e.current_contract_address();
+    if *payer != contract {
+        payment.transfer(payer, &contract, &total_price);
+    }
+    let mut fees_held = 0;
+    if fee > 0 {
+        // Payment token fees are held in the contract for the treasury to withdraw
+        if in_payment_token {
+            fees_held = fee;
+        } else {
+            payment.transfer(&contract, &state.treasury, &fee);
+        }
+    }
+    if royalty > 0 {
+        payment.transfer(&contract, &credit.original_farmer, &royalty);
+        if in_payment_token {
+            add_earnings(e, &state, &credit.original_farmer, royalty);
+        }
+    }
+    let proceeds = total_price - fee - royalty;
+    payment.transfer(&contract, &order.seller_address, &proceeds);
+    if in_payment_token {
+        add_earnings(e, &state, &order.seller_address, proceeds);
+    }
+
+    // Genuine fills refund the listing fee in proportion to the amount they sell
+    release_listing_fee(e, &state, &order.id, amount, true);
+
+    // Release the escrowed credits to the buyer along with a credit record of their own;
+    // a credit sold out no longer backs tokens of its own and is marked Merged
+    move_credits(e, &contract, buyer_address, amount);
+    let bought_id = derive_credit_id(e, &order.credit_id, b"_TRADE_");
+    let mut bought = credit.clone();
+    bought.id = bought_id.clone();
+    bought.farmer_address = buyer_address.clone();
+    bought.carbon_amount = amount;
+    bought.created_at = e.ledger().timestamp();
+    credit.carbon_amount -= amount;
+    if credit.carbon_amount == 0 {
+        credit.status = CreditStatus::Merged;
+        index_remove(e, &Index::FarmerCredits(credit.farmer_address.clone()), &credit.id);
+        unindex_catalog(e, &credit);
+    }
+    e.storage().persistent().set(&credit_key, &credit);
+    extend_ttl(e, &state, &credit_key);
+
+    let bought_key = DataKey::Credit(bought_id.clone());
+    e.storage().persistent().set(&bought_key, &bought);
+    extend_ttl(e, &state, &bought_key);
+    index_push(e, &state, &Index::FarmerCredits(buyer_address.clone()), &bought_id);
+    index_catalog(e, &state, &bought);
+    index_push(e, &state, &Index::AllCredits, &bought_id);
+    record_owner(e, &state, &bought_id, buyer_address);
+    record_provenance(e, &state, &order.credit_id, symbol_short!("trade"), buyer_address, amount);
+    record_provenance(e, &state, &bought_id, symbol_short!("trade"), buyer_address, amount);
+
+    // Keep the executed amount and price once the order leaves the active book
+    let fill_key = DataKey::OrderFill(order.id.clone());
+    let filled_amount = e.storage().persistent().get::<_, OrderFill>(&fill_key)
+        .map_or(0, |fill| fill.filled_amount);
+    e.storage().persistent().set(&fill_key, &OrderFill {
+        filled_amount: checked_add(e, filled_amount, amount),
+        last_price_per_ton: price_per_ton,
+        last_filled_at: e.ledger().timestamp(),
+    });
+    extend_ttl(e, &state, &fill_key);
+
+    // Record the trade in the credit's price history, dropping the oldest when full
+    if in_payment_token {
+        let history_key = DataKey::PriceHistory(order.credit_id.clone());
+        let mut history: Vec<TradePoint> = e.storage().persistent().get(&history_key)
+            .unwrap_or(Vec::new(e));
+        if history.len() >= MAX_PRICE_HISTORY {
+            history.pop_front();
+        }
+        let point = TradePoint {
+            price_per_ton,
+            amount,
+            timestamp: e.ledger().timestamp(),
+        };
+        history.push_back(point.clone());
+        e.storage().persistent().set(&history_key, &history);
+        extend_ttl(e, &state, &history_key);
+
+        // The bought credit's history starts with the trade that created it
+        let bought_history_key = DataKey::PriceHistory(bought_id.clone());
+        e.storage().persistent().set(&bought_history_key, &Vec::from_array(e, [point]));
+        extend_ttl(e, &state, &bought_history_key);
+
+        let vintage_key = DataKey::VintageTrades(credit.vintage);
+        let (tons, value): (i128, i128) = e.storage().persistent().get(&vintage_key).unwrap_or((0, 0));
+        e.storage().persistent().set(&vintage_key, &(checked_add(e, tons, amount), checked_add(e, value, total_price)));
+        extend_ttl(e, &state, &vintage_key);
+    }
+
+    // Append to the global feed, evicting the oldest trade when full
+    let mut trades: Vec<TradeRecord> = e.storage().persistent().get(&DataKey::RecentTrades)
+        .unwrap_or(Vec::new(e));
+    if trades.len() >= MAX_RECENT_TRADES {
+        trades.pop_front();
+    }
+    trades.push_back(TradeRecord {
+        order_id: order.id.clone(),
+        credit_id: order.credit_id.clone(),
+        seller: order.seller_address.clone(),
+        buyer: buyer_address.clone(),
+        price_per_ton,
+        amount,
+        timestamp: e.ledger().timestamp(),
+    });
+    e.storage().persistent().set(&DataKey::RecentTrades, &trades);
+    extend_ttl(e, &state, &DataKey::RecentTrades);
+
+    // Update market volume
+    let mut new_state = state;
+    new_state.accumulated_fees = checked_add(e, new_state.accumulated_fees, fees_held);
+    if in_payment_token {
+        new_state.total_market_volume = checked_add(e, new_state.total_market_volume, total_price);
+        new_state.total_credits_traded = checked_add(e, new_state.total_credits_traded, amount);
+    }
+    new_state.total_credits_listed -= amount;
+    e.storage().instance().set(&DataKey::State, &new_state);
+}
+
+/// Circuit breaker against fat-finger and manipulated trades: reject prices deviating
+/// beyond the configured bound from the average of the credit's latest trades
+/// Credits with too few trades have no reliable reference and are not checked
+fn require_price_in_bounds(e: &Env, state: &ContractState, credit_id: &String, price_per_ton: i128) {
+    if state.max_price_deviation_bps == 0 {
+        return;
+    }
+    let history: Vec<TradePoint> = e.storage().persistent()
+        .get(&DataKey::PriceHistory(credit_id.clone()))
+        .unwrap_or(Vec::new(e));
+    if history.len() < MIN_PRICE_REFERENCE_TRADES {
+        return;
+    }
+
+    let count = history.len().min(PRICE_REFERENCE_TRADES);
+    let mut sum: i128 = 0;
+    for trade in history.slice(history.len() - count..).iter() {
+        sum = checked_add(e, sum, trade.price_per_ton);
+    }
+    let reference = sum / count as i128;
+
+    let deviation = (price_per_ton - reference).abs();
+    if checked_mul(e, deviation, BPS_DENOMINATOR)
+        > checked_mul(e, reference, state.max_price_deviation_bps as i128)
+    {
+        panic_with_error!(e, CarbonCreditError::PriceOutOfBounds);
+    }
+}
+
+/// Token contract an order settles in, defaulting to the contract's payment token
+fn order_asset(e: &Env, state: &ContractState, order_id: &String) -> Address {
+    e.storage().persistent()
+        .get(&DataKey::OrderAsset(order_id.clone()))
+        .unwrap_or_else(|| state.payment_token.clone())
+}
+
+/// Load an escrow that has not been settled or refunded yet
+fn load_pending_escrow(e: &Env, escrow_key: &DataKey) -> Escrow {
+    let escrow: Escrow = e.storage().persistent().get(escrow_key)
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+    if escrow.status != String::from_str(e, "Pending") {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    escrow
+}
+
+/// Amount of a credit escrowed in its active orders and pending escrows
+fn reserved_amount(e: &Env, credit_id: &String) -> i128 {
+    e.storage().persistent().get(&DataKey::ReservedAmount(credit_id.clone())).unwrap_or(0)
+}
+
+/// Record the amount of a credit escrowed in its active orders and pending escrows
+fn set_reserved_amount(e: &Env, state: &ContractState, credit_id: &String, amount: i128) {
+    let key = DataKey::ReservedAmount(credit_id.clone());
+    if amount == 0 {
+        e.storage().persistent().remove(&key);
+    } else {
+        e.storage().persistent().set(&key, &amount);
+        extend_ttl(e, state, &key);
+    }
+}
+
+/// Release the share of an order's held listing fee covering `amount` of it, back to
+/// the payer for a sale or to the treasury for an amount that went unsold
+/// Shares round down and the last release takes what is left
+fn release_listing_fee(e: &Env, state: &ContractState, order_id: &String, amount: i128, refund: bool) {
+    let fee_key = DataKey::ListingFee(order_id.clone());
+    let (payer, held, covered): (Address, i128, i128) = match e.storage().persistent().get(&fee_key) {
+        Some(fee) => fee,
+        None => return,
+    };
+    let share = if amount >= covered {
+        e.storage().persistent().remove(&fee_key);
+        held
+    } else {
+        let share = checked_mul(e, held, amount) / covered;
+        e.storage().persistent().set(&fee_key, &(payer.clone(), held - share, covered - amount));
+        extend_ttl(e, state, &fee_key);
+        share
+    };
+    if share > 0 {
+        let to = if refund { payer } else { state.treasury.clone() };
+        token::Client::new(e, &state.payment_token).transfer(&e.current_contract_address(), &to, &share);
+    }
+}
+
+/// Add to the lifetime earnings of a seller or royalty recipient
+fn add_earnings(e: &Env, state: &ContractState, farmer: &Address, amount: i128) {
+    let key = DataKey::FarmerEarnings(farmer.clone());
+    let earnings: i128 = e.storage().persistent().get(&key).unwrap_or(0);
+    e.storage().persistent().set(&key, &checked_add(e, earnings, amount));
+    extend_ttl(e, state, &key);
+}
+
+/// Price per ton a buyer pays for an order right now
+fn order_price(e: &Env, order: &MarketOrder) -> i128 {
+    let auction: Option<Auction> = e.storage().persistent().get(&DataKey::Auction(order.id.clone()));
+    match auction {
+        Some(auction) => auction.price_at(e, e.ledger().timestamp()),
+        None => order.price_per_ton,
+    }
+}
+
+/// Add two amounts, failing with `ArithmeticOverflow` instead of wrapping
+fn checked_add(e: &Env, a: i128, b: i128) -> i128 {
+    a.checked_add(b)
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::ArithmeticOverflow))
+}
+
+/// Multiply two amounts, failing with `ArithmeticOverflow` instead of wrapping
+fn checked_mul(e: &Env, a: i128, b: i128) -> i128 {
+    a.checked_mul(b)
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::ArithmeticOverflow))
+}
+
+/// Cost of `amount` base units at `price_per_ton` whole tons, rounded up so that
+/// no purchase, however small, is free
+fn trade_price(e: &Env, amount: i128, price_per_ton: i128) -> i128 {
+    checked_add(e, checked_mul(e, amount, price_per_ton), TON - 1) / TON
+}
+
+/// Share of `amount` given in basis points, rounded per `mode`
+/// The payer of the share keeps whatever rounding leaves over
+fn apply_bps(e: &Env, amount: i128, bps: u32, mode: RoundingMode) -> i128 {
+    let scaled = checked_mul(e, amount, bps as i128);
+    match mode {
+        RoundingMode::Floor => scaled / BPS_DENOMINATOR,
+        RoundingMode::HalfUp => checked_add(e, scaled, BPS_DENOMINATOR / 2) / BPS_DENOMINATOR,
+    }
+}
+
+/// Burn `amount` of a credit from the retiree, who must own it, and issue a retirement
+/// certificate; the caller checks authorization
+fn retire_credit(
+    e: &Env,
+    credit_id: String,
+    retiree: &Address,
+    amount: i128,
+    reason: RetirementReason,
+    note: Option<String>,
+) -> String {
+    let mut credit: CarbonCredit = e.storage().persistent().get(&DataKey::Credit(credit_id.clone()))
+        .unwrap_or_else(|| panic_with_error!(e, CarbonCreditError::InvalidCreditData));
+
+    // Merged credits are retired through the credit they were merged into
+    if credit.status == CreditStatus::Merged {
+        panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+    }
+    // Credits cancelled after an upheld flag back no genuine removals
+    if credit.status == CreditStatus::Cancelled {
+        panic_with_error!(e, CarbonCreditError::VerificationFailed);
+    }
+    require_not_frozen(e, &credit_id);
+
+    // Only the owner may retire a credit, burning from their own balance
+    if *retiree != credit.farmer_address {
+        panic_with_error!(e, CarbonCreditError::NotAuthorized);
+    }
+    if amount <= 0 || Base::balance(e, retiree) < amount {
+        panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+    }
+    let mut state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+    require_aligned_amount(e, &state, amount);
+
+    // The credit can never be retired below zero, nor below what its orders and escrows hold
+    if amount > credit.carbon_amount - reserved_amount(e, &credit_id) {
+        panic_with_error!(e, CarbonCreditError::InsufficientBalance);
+    }
+
+    // Update credit amount
+    if credit.status == CreditStatus::Verified {
+        add_level_amount(e, &state, credit.verification_level, -amount);
+    }
+    credit.carbon_amount -= amount;
+    if credit.carbon_amount == 0 {
+        credit.status = CreditStatus::Retired;
+    }
+
+    // Store updated credit
+    e.storage().persistent().set(&DataKey::Credit(credit_id.clone()), &credit);
+
+    // Fully retired credits drop out of the farmer's index
+    if credit.carbon_amount == 0 {
+        index_remove(e, &Index::FarmerCredits(credit.farmer_address.clone()), &credit_id);
+        unindex_catalog(e, &credit);
+    }
+
+    // Update contract state
+    state.total_credits_retired = checked_add(e, state.total_credits_retired, amount);
+    state.total_tokens_burned = checked_add(e, state.total_tokens_burned, amount);
+    e.storage().instance().set(&DataKey::State, &state);
+    add_vintage_stats(e, &state, credit.vintage, 0, amount);
+
+    // Burn tokens
+    burn_credits(e, retiree, amount);
+    record_provenance(e, &state, &credit_id, symbol_short!("retire"), retiree, amount);
+
+    // Issue a retirement certificate
+    let retirements_key = DataKey::CreditRetirements(credit_id.clone());
+    let mut retirements: Vec<String> = e.storage().persistent().get(&retirements_key)
+        .unwrap_or(Vec::new(e));
+    let mut certificate_id = IdBuilder::new();
+    certificate_id.push_bytes(e, b"CERT_");
+    certificate_id.push_str(e, &credit_id);
+    certificate_id.push_bytes(e, b"_");
+    certificate_id.push_u64(e, retirements.len() as u64);
+    let certificate_id = certificate_id.build(e);
+
+    let certificate = RetirementCertificate {
+        id: certificate_id.clone(),
+        credit_id,
+        retiree: retiree.clone(),
+        amount,
+        reason,
+        note,
+        retired_at: e.ledger().timestamp(),
+    };
+    let certificate_key = DataKey::Certificate(certificate_id.clone());
+    e.storage().persistent().set(&certificate_key, &certificate);
+    extend_ttl(e, &state, &certificate_key);
+
+    retirements.push_back(certificate_id.clone());
+    e.storage().persistent().set(&retirements_key, &retirements);
+    extend_ttl(e, &state, &retirements_key);
+    index_push(e, &state, &Index::ReasonRetirements(reason), &certificate_id);
+    index_push(e, &state, &Index::RetireeCertificates(retiree.clone()), &certificate_id);
+
+    e.events().publish(
+        (symbol_short!("credit"), symbol_short!("retire")),
+        (certificate_id.clone(), amount, reason),
+    );
+
+    certificate_id
+}
+
+/// Move credit tokens between accounts whose authorization was already checked
+fn move_credits(e: &Env, from: &Address, to: &Address, amount: i128) {
+    require_tokens_unfrozen(e);
+    Base::update(e, Some(from), Some(to), amount);
+    emit_transfer(e, from, to, amount);
+}
+
+/// Burn credit tokens from an account whose authorization was already checked
+fn burn_credits(e: &Env, from: &Address, amount: i128) {
+    require_tokens_unfrozen(e);
+    Base::update(e, Some(from), None, amount);
+    emit_burn(e, from, amount);
+}
+
+/// Fixed-capacity buffer for building identifiers, since the contract has no allocator
+struct IdBuilder {
+    buf: [u8; MAX_ID_LEN],
+    len: usize,
+}
+
+impl IdBuilder {
+    fn new() -> Self {
+        IdBuilder { buf: [0; MAX_ID_LEN], len: 0 }
+    }
+
+    fn push_bytes(&mut self, e: &Env, bytes: &[u8]) {
+        let end = self.len + bytes.len();
+        if end > MAX_ID_LEN {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        self.buf[self.len..end].copy_from_slice(bytes);
+        self.len = end;
+    }
+
+    fn push_str(&mut self, e: &Env, s: &String) {
+        let end = self.len + s.len() as usize;
+        if end > MAX_ID_LEN {
+            panic_with_error!(e, CarbonCreditError::InvalidCreditData);
+        }
+        s.copy_into_slice(&mut self.buf[self.len..end]);
+        self.len = end;
+    }
+
+    fn push_u64(&mut self, e: &Env, mut n: u64) {
+        let mut digits = [0u8; 20];
+        let mut i = digits.len();
+        loop {
+            i -= 1;
+            digits[i] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        self.push_bytes(e, &digits[i..]);
+    }
+
+    fn push_i64(&mut self, e: &Env, n: i64) {
+        if n < 0 {
+            self.push_bytes(e, b"-");
+        }
+        self.push_u64(e, n.unsigned_abs());
+    }
+
+    fn build(&self, e: &Env) -> String {
+        String::from_bytes(e, &self.buf[..self.len])
+    }
+}
+
+#[cfg(test)]
+mod test;
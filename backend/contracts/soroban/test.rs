@@ -0,0 +1,4023 @@
+#![cfg(test)]
+extern crate std;
+
+use super::*;
+use soroban_sdk::testutils::{Address as _, Events as _, Ledger as _, MockAuth, MockAuthInvoke};
+use soroban_sdk::{vec, InvokeError};
+
+/// Ledger time the tests start at, 2025-01-01T00:00:00Z
+const NOW: u64 = 1_735_689_600;
+
+/// Register the contract with a fresh admin and a Stellar asset as payment token
+fn setup(e: &Env) -> (CarbonCreditTokenClient<'_>, Address, token::StellarAssetClient<'_>) {
+    e.ledger().set_timestamp(NOW);
+    let admin = Address::generate(e);
+    let payment = e.register_stellar_asset_contract_v2(admin.clone()).address();
+    let contract_id = e.register(CarbonCreditToken, (admin.clone(), payment.clone()));
+    (
+        CarbonCreditTokenClient::new(e, &contract_id),
+        admin,
+        token::StellarAssetClient::new(e, &payment),
+    )
+}
+
+/// A well-formed CIDv0 report hash unique to `tag`
+fn report_hash(e: &Env, tag: &str) -> String {
+    let hash = std::format!("Qm{:a<44}", tag.replace(['0', 'O', 'I', 'l'], "z"));
+    String::from_str(e, &hash)
+}
+
+fn mint_request(e: &Env, farmer: &Address, farm: &str, season: &str, amount: i128) -> MintRequest {
+    MintRequest {
+        farmer_address: farmer.clone(),
+        farm_id: String::from_str(e, farm),
+        season_id: String::from_str(e, season),
+        carbon_amount: amount,
+        verification_level: VerificationLevel::Basic,
+        methodology: String::from_str(e, "IPCC 2019"),
+        vintage: 2023,
+        report_hash: report_hash(e, &std::format!("{farm}{season}")),
+        coordinates: vec![e, 225_000_000, 806_000_000],
+        metadata: Map::new(e),
+    }
+}
+
+/// Mint a credit that still awaits verification
+fn mint_pending(
+    e: &Env,
+    client: &CarbonCreditTokenClient,
+    farmer: &Address,
+    farm: &str,
+    season: &str,
+    amount: i128,
+) -> String {
+    let request = mint_request(e, farmer, farm, season, amount);
+    client.mint_carbon_credit(
+        &request.farmer_address,
+        &request.farm_id,
+        &request.season_id,
+        &request.carbon_amount,
+        &request.verification_level,
+        &request.methodology,
+        &request.vintage,
+        &request.report_hash,
+        &request.coordinates,
+        &request.metadata,
+    )
+}
+
+/// Mint a credit and verify it so it can be traded
+fn mint_verified(
+    e: &Env,
+    client: &CarbonCreditTokenClient,
+    farmer: &Address,
+    farm: &str,
+    season: &str,
+    amount: i128,
+) -> String {
+    let credit_id = mint_pending(e, client, farmer, farm, season, amount);
+    let verifier = Address::generate(e);
+    client.add_verifier(&verifier);
+    client.verify_credit(&verifier, &credit_id, &VerificationLevel::Basic);
+    credit_id
+}
+
+/// Address whose authorization the last contract call required
+fn last_signer(e: &Env) -> Address {
+    let auths = e.auths();
+    assert_eq!(auths.len(), 1);
+    auths[0].0.clone()
+}
+
+#[test]
+fn test_mint_requires_admin_auth() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let farmer = Address::generate(&e);
+
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    assert_eq!(last_signer(&e), admin);
+    let credit = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(credit.farmer_address, farmer);
+    assert_eq!(credit.carbon_amount, 100 * TON);
+    assert_eq!(client.balance(&farmer), 100 * TON);
+}
+
+#[test]
+fn test_mint_without_admin_auth_fails() {
+    let e = Env::default();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let request = mint_request(&e, &farmer, "F1", "S1", 100 * TON);
+
+    // Only the farmer signs, which does not satisfy the admin check
+    e.mock_auths(&[MockAuth {
+        address: &farmer,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "mint_carbon_credit",
+            args: (
+                request.farmer_address.clone(),
+                request.farm_id.clone(),
+                request.season_id.clone(),
+                request.carbon_amount,
+                request.verification_level,
+                request.methodology.clone(),
+                request.vintage,
+                request.report_hash.clone(),
+                request.coordinates.clone(),
+                request.metadata.clone(),
+            )
+                .into_val(&e),
+            sub_invokes: &[],
+        },
+    }]);
+    let result = client.try_mint_carbon_credit(
+        &request.farmer_address,
+        &request.farm_id,
+        &request.season_id,
+        &request.carbon_amount,
+        &request.verification_level,
+        &request.methodology,
+        &request.vintage,
+        &request.report_hash,
+        &request.coordinates,
+        &request.metadata,
+    );
+    assert!(result.is_err());
+    assert_eq!(client.get_credit_count(), 0);
+}
+
+#[test]
+fn test_management_calls_require_admin_auth() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+
+    client.update_market_settings(&true, &VerificationLevel::Standard);
+    assert_eq!(last_signer(&e), admin);
+    client.pause_market();
+    assert_eq!(last_signer(&e), admin);
+    client.resume_market();
+    assert_eq!(last_signer(&e), admin);
+}
+
+#[test]
+fn test_owner_calls_require_owner_auth() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.list_for_sale(&credit_id, &5_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(last_signer(&e), farmer);
+    client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(last_signer(&e), farmer);
+}
+
+#[test]
+fn test_buy_pays_seller_price_per_whole_ton() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &5_000_000, &(100 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(40 * TON), &i128::MAX);
+
+    assert_eq!(client.balance(&buyer), 40 * TON);
+    assert_eq!(payment_token.balance(&farmer), 200_000_000);
+    assert_eq!(payment_token.balance(&buyer), 800_000_000);
+    assert_eq!(client.get_contract_stats().total_market_volume, 200_000_000);
+}
+
+#[test]
+fn test_fractional_purchase_rounds_up() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    // One base unit at 5 per ton costs 0.000005, which would round to nothing
+    let order_id = client.list_for_sale(&credit_id, &5, &(100 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &1, &i128::MAX);
+
+    assert_eq!(token::Client::new(&e, &payment.address).balance(&farmer), 1);
+}
+
+#[test]
+fn test_fill_moves_credit_record_to_buyer() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(100 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(60 * TON), &i128::MAX);
+
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 40 * TON);
+    let bought = client.list_credits_by_farmer(&buyer, &0, &10);
+    assert_eq!(bought.len(), 1);
+    let bought = bought.get_unchecked(0);
+    assert_eq!(bought.farmer_address, buyer);
+    assert_eq!(bought.original_farmer, farmer);
+    assert_eq!(bought.carbon_amount, 60 * TON);
+    assert_eq!(bought.status, CreditStatus::Verified);
+
+    // Selling out leaves the seller's record with nothing behind it
+    client.buy_carbon_credits(&order_id, &buyer, &(40 * TON), &i128::MAX);
+    let sold = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(sold.carbon_amount, 0);
+    assert_eq!(sold.status, CreditStatus::Merged);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &0, &10).len(), 0);
+    assert_eq!(client.list_credits_by_farmer(&buyer, &0, &10).len(), 2);
+    assert_eq!(client.balance(&buyer), 100 * TON);
+}
+
+#[test]
+fn test_depleted_order_cannot_be_bought_again() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(4 * TON), &i128::MAX);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 6 * TON);
+    client.buy_carbon_credits(&order_id, &buyer, &(6 * TON), &i128::MAX);
+
+    let order = client.get_market_order(&order_id).unwrap();
+    assert_eq!(order.amount, 0);
+    assert_eq!(order.status, String::from_str(&e, "Filled"));
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::OrderDepleted))
+    );
+}
+
+#[test]
+fn test_escrowed_amount_stays_reserved_until_settled() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let escrow_id = client.create_escrow_order(&order_id, &buyer, &(4 * TON));
+    client.cancel_order(&order_id, &None);
+    assert_eq!(client.get_reserved_amount(&credit_id), 4 * TON);
+    assert_eq!(client.get_available_amount(&credit_id), 96 * TON);
+
+    client.settle_escrow(&admin, &escrow_id);
+    assert_eq!(client.get_reserved_amount(&credit_id), 0);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 96 * TON);
+    assert_eq!(client.list_credits_by_farmer(&buyer, &0, &10).get_unchecked(0).carbon_amount, 4 * TON);
+}
+
+#[test]
+fn test_buyer_retires_purchased_credits() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(40 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(40 * TON), &i128::MAX);
+    let bought_id = client.list_credits_by_farmer(&buyer, &0, &1).get_unchecked(0).id;
+
+    let certificate_id = client.retire_credits(&bought_id, &buyer, &(40 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    let certificate = client.get_retirement_certificate(&certificate_id).unwrap();
+    assert_eq!(certificate.retiree, buyer);
+    assert_eq!(certificate.credit_id, bought_id);
+    assert_eq!(client.balance(&buyer), 0);
+    assert_eq!(client.get_carbon_credit(&bought_id).unwrap().status, CreditStatus::Retired);
+    assert_eq!(
+        client.try_retire_credits(&bought_id, &buyer, &1, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+}
+
+#[test]
+fn test_holder_cannot_retire_another_owners_credit() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let other = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    mint_verified(&e, &client, &other, "F2", "S1", 100 * TON);
+
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &other, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON);
+}
+
+#[test]
+fn test_retirement_cannot_dip_into_listed_amount() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let other_id = mint_verified(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    client.list_for_sale(&credit_id, &1_000_000, &(70 * TON), &None, &None, &None);
+
+    // The farmer's other credit keeps the balance up, but only 30 tons of this one are unlisted
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &(31 * TON), &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+    client.retire_credits(&credit_id, &farmer, &(30 * TON), &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(client.get_available_amount(&credit_id), 0);
+    assert_eq!(client.get_carbon_credit(&other_id).unwrap().carbon_amount, 100 * TON);
+}
+
+#[test]
+fn test_flag_then_clear_restores_trading() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.flag_credit(&auditor, &credit_id, &String::from_str(&e, "double counted"));
+    assert!(client.is_credit_flagged(&credit_id));
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+
+    client.resolve_flag(&verifier, &credit_id, &false);
+    assert!(!client.is_credit_flagged(&credit_id));
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Verified);
+    client.list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None);
+}
+
+#[test]
+fn test_upheld_flag_cancels_credit_for_good() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.flag_credit(&auditor, &credit_id, &String::from_str(&e, "fabricated report"));
+    client.resolve_flag(&verifier, &credit_id, &true);
+
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Cancelled);
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Cancelled);
+}
+
+#[test]
+fn test_only_unresolved_flags_count_towards_cap() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let spammer = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let reason = String::from_str(&e, "spam");
+
+    for _ in 0..MAX_FLAGS {
+        client.flag_credit(&spammer, &credit_id, &reason);
+    }
+    assert_eq!(
+        client.try_flag_credit(&auditor, &credit_id, &reason),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    client.resolve_flag(&verifier, &credit_id, &false);
+    client.flag_credit(&auditor, &credit_id, &String::from_str(&e, "audit finding"));
+    let flags = client.get_credit_flags(&credit_id);
+    assert_eq!(flags.len(), MAX_FLAGS);
+    assert_eq!(flags.last_unchecked().flagger, auditor);
+    assert!(client.is_credit_flagged(&credit_id));
+}
+
+#[test]
+fn test_flagging_is_paused_with_the_contract() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.flag_credit(&auditor, &credit_id, &String::from_str(&e, "suspicious"));
+
+    client.pause_contract();
+    assert_eq!(
+        client.try_flag_credit(&auditor, &credit_id, &String::from_str(&e, "again")),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_resolve_flag(&verifier, &credit_id, &true),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+}
+
+#[test]
+fn test_listing_within_capacity_and_over_it() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.list_for_sale(&credit_id, &1_000_000, &(60 * TON), &None, &None, &None);
+    client.list_for_sale(&credit_id, &2_000_000, &(40 * TON), &None, &None, &None);
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &1, &None, &None, &None),
+        Err(Ok(CarbonCreditError::OverListed))
+    );
+}
+
+#[test]
+fn test_sold_amount_cannot_be_listed_again() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    // Tokens backing another credit must not cover a relisting of sold tons
+    mint_verified(&e, &client, &farmer, "F1", "S2", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(60 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(60 * TON), &i128::MAX);
+
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(41 * TON), &None, &None, &None),
+        Err(Ok(CarbonCreditError::OverListed))
+    );
+    client.list_for_sale(&credit_id, &1_000_000, &(40 * TON), &None, &None, &None);
+    assert_eq!(client.get_available_amount(&credit_id), 0);
+}
+
+#[test]
+fn test_portfolio_tracks_bought_and_sold_tons() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let mut request = mint_request(&e, &farmer, "F2", "S1", 50 * TON);
+    request.vintage = 2024;
+    let second_id = client.mint_carbon_credit(
+        &request.farmer_address,
+        &request.farm_id,
+        &request.season_id,
+        &request.carbon_amount,
+        &request.verification_level,
+        &request.methodology,
+        &request.vintage,
+        &request.report_hash,
+        &request.coordinates,
+        &request.metadata,
+    );
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    client.verify_credit(&verifier, &second_id, &VerificationLevel::Basic);
+
+    let first_order = client.list_for_sale(&first_id, &2_000_000, &(30 * TON), &None, &None, &None);
+    let second_order = client.list_for_sale(&second_id, &3_000_000, &(20 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&first_order, &buyer, &(30 * TON), &i128::MAX);
+    client.buy_carbon_credits(&second_order, &buyer, &(20 * TON), &i128::MAX);
+
+    let portfolio = client.get_buyer_portfolio(&buyer);
+    assert_eq!(portfolio.total_tons, 50 * TON);
+    assert_eq!(portfolio.tons_by_vintage.get(2023), Some(30 * TON));
+    assert_eq!(portfolio.tons_by_vintage.get(2024), Some(20 * TON));
+    assert_eq!(portfolio.market_value, 30 * 2_000_000 + 20 * 3_000_000);
+
+    // The seller keeps only what they have not sold
+    let portfolio = client.get_buyer_portfolio(&farmer);
+    assert_eq!(portfolio.total_tons, 100 * TON);
+    assert_eq!(portfolio.market_value, 70 * 2_000_000 + 30 * 3_000_000);
+}
+
+#[test]
+fn test_orders_settle_in_their_price_asset() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let usdc = token::StellarAssetClient::new(&e, &e.register_stellar_asset_contract_v2(admin).address());
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    usdc.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &Some(usdc.address.clone()), &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    client.approve_price_asset(&usdc.address);
+    let in_usdc = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &Some(usdc.address.clone()), &None);
+    let in_payment = client.list_for_sale(&credit_id, &3_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(client.get_order_asset(&in_usdc), usdc.address);
+    assert_eq!(client.get_order_asset(&in_payment), payment.address);
+
+    client.buy_carbon_credits(&in_usdc, &buyer, &(5 * TON), &i128::MAX);
+    client.buy_carbon_credits(&in_payment, &buyer, &(5 * TON), &i128::MAX);
+    assert_eq!(token::Client::new(&e, &usdc.address).balance(&farmer), 5_000_000);
+    assert_eq!(token::Client::new(&e, &payment.address).balance(&farmer), 15_000_000);
+
+    // Volume and traded tons only count the payment token, keeping the average price honest
+    let stats = client.get_contract_stats();
+    assert_eq!(stats.total_market_volume, 15_000_000);
+    assert_eq!(stats.total_credits_traded, 5 * TON);
+    assert_eq!(client.get_impact_summary().average_price, 3_000_000);
+}
+
+/// Oracle key pair registered as the contract's verifier key
+fn oracle_key(e: &Env, client: &CarbonCreditTokenClient) -> ed25519_dalek::SigningKey {
+    let key = ed25519_dalek::SigningKey::from_bytes(&[7; 32]);
+    client.update_verifier_pubkey(&Some(BytesN::from_array(e, &key.verifying_key().to_bytes())));
+    key
+}
+
+/// The oracle's signature over the SHA-256 hash of a mint request's XDR encoding
+fn attest(e: &Env, key: &ed25519_dalek::SigningKey, request: &MintRequest) -> BytesN<64> {
+    use ed25519_dalek::Signer;
+    let digest = e.crypto().sha256(&request.clone().to_xdr(e)).to_array();
+    BytesN::from_array(e, &key.sign(&digest).to_bytes())
+}
+
+#[test]
+fn test_attested_mint_checks_oracle_signature() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let key = oracle_key(&e, &client);
+    let farmer = Address::generate(&e);
+    let request = mint_request(&e, &farmer, "F1", "S1", 100 * TON);
+    let signature = attest(&e, &key, &request);
+
+    // The host traps on a signature that does not match the request
+    let mut tampered = request.clone();
+    tampered.carbon_amount = 1_000 * TON;
+    assert_eq!(
+        client.try_mint_attested_carbon_credit(&tampered, &signature),
+        Err(Err(InvokeError::Abort))
+    );
+    assert_eq!(client.balance(&farmer), 0);
+
+    let credit_id = client.mint_attested_carbon_credit(&request, &signature);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON);
+    assert_eq!(client.balance(&farmer), 100 * TON);
+}
+
+#[test]
+fn test_unsigned_mint_rejected_once_oracle_key_set() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    oracle_key(&e, &client);
+    let farmer = Address::generate(&e);
+    let request = mint_request(&e, &farmer, "F1", "S1", 100 * TON);
+
+    assert_eq!(
+        client.try_mint_carbon_credit(
+            &request.farmer_address,
+            &request.farm_id,
+            &request.season_id,
+            &request.carbon_amount,
+            &request.verification_level,
+            &request.methodology,
+            &request.vintage,
+            &request.report_hash,
+            &request.coordinates,
+            &request.metadata,
+        ),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+}
+
+#[test]
+fn test_batch_and_locked_mints_accept_oracle_signatures() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let key = oracle_key(&e, &client);
+    let farmer = Address::generate(&e);
+    let first = mint_request(&e, &farmer, "F1", "S1", 10 * TON);
+    let second = mint_request(&e, &farmer, "F2", "S1", 20 * TON);
+    let locked = mint_request(&e, &farmer, "F3", "S1", 30 * TON);
+
+    assert_eq!(
+        client.try_batch_mint_carbon_credits(&vec![&e, first.clone(), second.clone()], &vec![&e, attest(&e, &key, &first)]),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    let signatures = vec![&e, attest(&e, &key, &first), attest(&e, &key, &second)];
+    assert_eq!(client.batch_mint_carbon_credits(&vec![&e, first, second], &signatures).len(), 2);
+
+    assert_eq!(
+        client.try_mint_locked_carbon_credit(&locked, &(NOW + 1_000), &None),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    let credit_id = client.mint_locked_carbon_credit(&locked, &(NOW + 1_000), &Some(attest(&e, &key, &locked)));
+    assert_eq!(client.get_unlock_at(&credit_id), NOW + 1_000);
+    assert_eq!(client.balance(&farmer), 60 * TON);
+}
+
+#[test]
+fn test_listing_fee_refunded_in_proportion_to_sales() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&farmer, &1_000);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_list_fee(&1_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(100 * TON), &None, &None, &None);
+    assert_eq!(payment_token.balance(&farmer), 0);
+
+    // An accomplice buying a token amount only gets a token share of the fee back
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    assert_eq!(payment_token.balance(&farmer), 1_000_000 + 10);
+
+    client.buy_carbon_credits(&order_id, &buyer, &(49 * TON), &i128::MAX);
+    assert_eq!(payment_token.balance(&farmer), 50_000_000 + 500);
+
+    client.cancel_order(&order_id, &None);
+    assert_eq!(payment_token.balance(&admin), 500);
+    assert_eq!(payment_token.balance(&client.address), 0);
+}
+
+#[test]
+fn test_listing_fee_refunded_in_full_when_order_sells_out() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&farmer, &1_000);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_list_fee(&1_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(3 * TON), &None, &None, &None);
+    for _ in 0..3 {
+        client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    }
+    assert_eq!(payment_token.balance(&farmer), 3_000_000 + 1_000);
+    assert_eq!(payment_token.balance(&client.address), 0);
+}
+
+#[test]
+fn test_no_listing_fee_when_disabled() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.cancel_order(&order_id, &None);
+    assert_eq!(token::Client::new(&e, &payment.address).balance(&client.address), 0);
+}
+
+#[test]
+fn test_member_lists_group_credit_but_outsider_cannot() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let group = Address::generate(&e);
+    let member = Address::generate(&e);
+    let outsider = Address::generate(&e);
+    client.add_member(&group, &member);
+    let credit_id = mint_verified(&e, &client, &group, "G1", "S1", 100 * TON);
+
+    client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &Some(member.clone()));
+    assert_eq!(last_signer(&e), member);
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &Some(outsider)),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_member_signature_satisfies_every_owner_check() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let group = Address::generate(&e);
+    let member = Address::generate(&e);
+    let recipient = Address::generate(&e);
+    client.add_member(&group, &member);
+    let acting = Some(member.clone());
+    let credit_id = mint_verified(&e, &client, &group, "G1", "S1", 100 * TON);
+    let other_id = mint_verified(&e, &client, &group, "G1", "S2", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &acting);
+    client.update_order_price(&order_id, &2_000_000, &acting);
+    assert_eq!(last_signer(&e), member);
+    client.cancel_order(&order_id, &acting);
+    assert_eq!(last_signer(&e), member);
+
+    let split_id = client.split_credit(&credit_id, &(20 * TON), &acting);
+    assert_eq!(last_signer(&e), member);
+    let merged_id = client.merge_credits(&vec![&e, credit_id, other_id], &acting);
+    assert_eq!(last_signer(&e), member);
+    client.distribute_credits(&merged_id, &vec![&e, (recipient.clone(), 10 * TON)], &acting);
+    assert_eq!(last_signer(&e), member);
+    client.transfer_credit_ownership(&split_id, &recipient, &acting);
+    assert_eq!(last_signer(&e), member);
+    assert_eq!(client.get_carbon_credit(&split_id).unwrap().farmer_address, recipient);
+
+    assert_eq!(
+        client.try_transfer_credit_ownership(&merged_id, &recipient, &Some(recipient.clone())),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_order_book_sorted_by_price() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let mid = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    let high = client.list_for_sale(&credit_id, &3_000_000, &(10 * TON), &None, &None, &None);
+    let low = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    let book = client.get_order_book(&credit_id, &10);
+    assert_eq!(book.len(), 3);
+    assert_eq!(book.get_unchecked(0).id, low);
+    assert_eq!(book.get_unchecked(1).id, mid);
+    assert_eq!(book.get_unchecked(2).id, high);
+    assert_eq!(client.get_order_book(&credit_id, &2).len(), 2);
+}
+
+#[test]
+fn test_order_book_sorts_auctions_by_decayed_price() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let fixed = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    let auction = client.list_for_auction(&credit_id, &5_000_000, &1_000_000, &1_000, &(10 * TON), &None);
+    assert_eq!(client.get_order_book(&credit_id, &10).get_unchecked(0).id, fixed);
+
+    // Halfway through, the auction has decayed to 3; at the end it rests at its floor of 1
+    e.ledger().set_timestamp(NOW + 500);
+    assert_eq!(client.current_auction_price(&auction), Some(3_000_000));
+    assert_eq!(client.get_order_book(&credit_id, &10).get_unchecked(0).id, fixed);
+    e.ledger().set_timestamp(NOW + 1_000);
+    assert_eq!(client.get_order_book(&credit_id, &10).get_unchecked(0).id, auction);
+}
+
+#[test]
+fn test_split_and_distribute_only_unreserved_tons_of_listed_credit() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let member = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.list_for_sale(&credit_id, &1_000_000, &(60 * TON), &None, &None, &None);
+
+    assert_eq!(
+        client.try_split_credit(&credit_id, &(41 * TON), &None),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+    let split_id = client.split_credit(&credit_id, &(20 * TON), &None);
+    assert_eq!(client.get_carbon_credit(&split_id).unwrap().carbon_amount, 20 * TON);
+
+    assert_eq!(
+        client.try_distribute_credits(&credit_id, &vec![&e, (member.clone(), 21 * TON)], &None),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+    client.distribute_credits(&credit_id, &vec![&e, (member.clone(), 20 * TON)], &None);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 60 * TON);
+    assert_eq!(client.get_available_amount(&credit_id), 0);
+}
+
+#[test]
+fn test_distribute_pooled_credit_to_three_members() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let group = Address::generate(&e);
+    let members = [Address::generate(&e), Address::generate(&e), Address::generate(&e)];
+    let amounts = [30 * TON, 30 * TON, 40 * TON];
+    let credit_id = mint_verified(&e, &client, &group, "G1", "S1", 100 * TON);
+
+    let mut recipients = Vec::new(&e);
+    for (member, amount) in members.iter().zip(amounts) {
+        recipients.push_back((member.clone(), amount));
+    }
+    let child_ids = client.distribute_credits(&credit_id, &recipients, &None);
+
+    assert_eq!(child_ids.len(), 3);
+    for (i, (member, amount)) in members.iter().zip(amounts).enumerate() {
+        let child_id = child_ids.get_unchecked(i as u32);
+        let child = client.get_carbon_credit(&child_id).unwrap();
+        assert_eq!(child.farmer_address, *member);
+        assert_eq!(child.carbon_amount, amount);
+        assert_eq!(child.vintage, 2023);
+        assert_eq!(child.status, CreditStatus::Verified);
+        assert_eq!(client.balance(member), amount);
+        assert_eq!(client.list_credits_by_farmer(member, &0, &10).get_unchecked(0).id, child_id);
+    }
+
+    // Distributed in full, the pooled credit no longer backs any tokens
+    let pooled = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(pooled.carbon_amount, 0);
+    assert_eq!(pooled.status, CreditStatus::Merged);
+    assert_eq!(client.balance(&group), 0);
+    assert_eq!(client.list_credits_by_farmer(&group, &0, &10).len(), 0);
+}
+
+#[test]
+fn test_distribute_with_one_invalid_amount_changes_nothing() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let group = Address::generate(&e);
+    let first = Address::generate(&e);
+    let second = Address::generate(&e);
+    let third = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &group, "G1", "S1", 100 * TON);
+    let credit_count = client.get_credit_count();
+
+    assert_eq!(
+        client.try_distribute_credits(
+            &credit_id,
+            &vec![&e, (first.clone(), 10 * TON), (second.clone(), 0), (third.clone(), 10 * TON)],
+            &None,
+        ),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    let pooled = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(pooled.carbon_amount, 100 * TON);
+    assert_eq!(pooled.status, CreditStatus::Verified);
+    assert_eq!(client.balance(&group), 100 * TON);
+    assert_eq!(client.get_credit_count(), credit_count);
+    for member in [first, second, third] {
+        assert_eq!(client.balance(&member), 0);
+        assert_eq!(client.list_credits_by_farmer(&member, &0, &10).len(), 0);
+    }
+}
+
+#[test]
+fn test_credit_with_reserved_tons_cannot_be_merged() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let second_id = mint_verified(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    let order_id = client.list_for_sale(&first_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    assert_eq!(
+        client.try_merge_credits(&vec![&e, first_id.clone(), second_id.clone()], &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    client.cancel_order(&order_id, &None);
+    let merged_id = client.merge_credits(&vec![&e, first_id, second_id], &None);
+    assert_eq!(client.get_carbon_credit(&merged_id).unwrap().carbon_amount, 200 * TON);
+}
+
+#[test]
+fn test_cancelled_order_cannot_be_bought() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(40 * TON), &None, &None, &None);
+    assert_eq!(client.balance(&farmer), 60 * TON);
+    client.cancel_order(&order_id, &None);
+
+    assert_eq!(last_signer(&e), farmer);
+    assert_eq!(client.balance(&farmer), 100 * TON);
+    assert_eq!(client.get_market_order(&order_id).unwrap().status, String::from_str(&e, "Cancelled"));
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_order_cannot_be_cancelled_twice() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(40 * TON), &None, &None, &None);
+    client.cancel_order(&order_id, &None);
+    assert_eq!(
+        client.try_cancel_order(&order_id, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(client.balance(&farmer), 100 * TON);
+}
+
+#[test]
+fn test_basic_credit_cannot_be_bought_below_premium_minimum() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    client.update_market_settings(&true, &VerificationLevel::Premium);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    assert!(VerificationLevel::Basic.rank() < VerificationLevel::Premium.rank());
+    assert!(VerificationLevel::Premium.rank() < VerificationLevel::Gold.rank());
+}
+
+#[test]
+fn test_migrate_credit_types_legacy_verification_level() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = String::from_str(&e, "F1_S1_2023_1");
+    let legacy = LegacyCarbonCredit {
+        id: credit_id.clone(),
+        farmer_address: farmer.clone(),
+        farm_id: String::from_str(&e, "F1"),
+        season_id: String::from_str(&e, "S1"),
+        carbon_amount: 10 * TON,
+        verification_level: String::from_str(&e, "Premium"),
+        methodology: String::from_str(&e, "IPCC 2019"),
+        vintage: 2023,
+        report_hash: report_hash(&e, "F1S1"),
+        created_at: NOW,
+        status: String::from_str(&e, "Verified"),
+        coordinates: vec![&e, 225_000_000, 806_000_000],
+        metadata: Map::new(&e),
+    };
+    e.as_contract(&client.address, || e.storage().instance().set(&credit_id, &legacy));
+
+    client.migrate_credit(&credit_id);
+
+    let credit = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(credit.verification_level, VerificationLevel::Premium);
+    assert_eq!(credit.status, CreditStatus::Verified);
+    assert_eq!(credit.original_farmer, farmer);
+    assert_eq!(
+        client.try_migrate_credit(&credit_id),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_min_verification_level_gates_each_sale() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.add_verifier(&verifier);
+    let gold_id = mint_pending(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.verify_credit(&verifier, &gold_id, &VerificationLevel::Gold);
+    let standard_id = mint_pending(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    client.verify_credit(&verifier, &standard_id, &VerificationLevel::Standard);
+    let gold_order = client.list_for_sale(&gold_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let standard_order = client.list_for_sale(&standard_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(client.get_market_order(&gold_order).unwrap().credit_id, gold_id);
+
+    client.update_market_settings(&true, &VerificationLevel::Premium);
+    client.buy_carbon_credits(&gold_order, &buyer, &TON, &i128::MAX);
+    assert_eq!(client.balance(&buyer), TON);
+    assert_eq!(
+        client.try_buy_carbon_credits(&standard_order, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+}
+
+/// Data of the last event the contract published under `topics` in the last call
+fn event_data(e: &Env, client: &CarbonCreditTokenClient, topics: (Symbol, Symbol)) -> Option<Val> {
+    let topics: Vec<Val> = topics.into_val(e);
+    e.events().all().iter()
+        .filter(|(contract, event_topics, _)| *contract == client.address && *event_topics == topics)
+        .map(|(_, _, data)| data)
+        .last()
+}
+
+#[test]
+fn test_mint_trade_and_retire_publish_events() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let data = event_data(&e, &client, (symbol_short!("credit"), symbol_short!("mint"))).unwrap();
+    assert_eq!(<(Address, i128)>::try_from_val(&e, &data).unwrap(), (farmer.clone(), 100 * TON));
+
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    client.verify_credit(&verifier, &credit_id, &VerificationLevel::Basic);
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(5 * TON), &i128::MAX);
+    let data = event_data(&e, &client, (symbol_short!("market"), symbol_short!("buy"))).unwrap();
+    assert_eq!(
+        <(String, Address, i128, i128)>::try_from_val(&e, &data).unwrap(),
+        (order_id, buyer, 5 * TON, 10_000_000)
+    );
+
+    let certificate_id = client.retire_credits(&credit_id, &farmer, &(3 * TON), &RetirementReason::Donation, &None);
+    let data = event_data(&e, &client, (symbol_short!("credit"), symbol_short!("retire"))).unwrap();
+    assert_eq!(
+        <(String, i128, RetirementReason)>::try_from_val(&e, &data).unwrap(),
+        (certificate_id, 3 * TON, RetirementReason::Donation)
+    );
+}
+
+#[test]
+fn test_list_credits_by_farmer_pages_in_mint_order() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let other = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 20 * TON);
+    let third_id = mint_pending(&e, &client, &farmer, "F1", "S3", 30 * TON);
+    mint_pending(&e, &client, &other, "F2", "S1", 10 * TON);
+
+    let page = client.list_credits_by_farmer(&farmer, &0, &2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get_unchecked(0).id, first_id);
+    assert_eq!(page.get_unchecked(1).id, second_id);
+    let page = client.list_credits_by_farmer(&farmer, &2, &2);
+    assert_eq!(page.len(), 1);
+    assert_eq!(page.get_unchecked(0).id, third_id);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &3, &2).len(), 0);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &10, &2).len(), 0);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &u32::MAX, &u32::MAX).len(), 0);
+
+    // A fully retired credit leaves the index
+    client.retire_credits(&second_id, &farmer, &(20 * TON), &RetirementReason::VoluntaryOffset, &None);
+    let page = client.list_credits_by_farmer(&farmer, &0, &10);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page.get_unchecked(1).id, third_id);
+}
+
+#[test]
+fn test_credits_and_state_use_separate_keys() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 20 * TON);
+
+    let stats = client.get_contract_stats();
+    assert_eq!(stats.admin, admin);
+    assert_eq!(stats.total_credits_minted, 30 * TON);
+    assert_eq!(client.get_carbon_credit(&first_id).unwrap().carbon_amount, 10 * TON);
+    assert_eq!(client.get_carbon_credit(&second_id).unwrap().carbon_amount, 20 * TON);
+    e.as_contract(&client.address, || {
+        assert!(e.storage().persistent().has(&DataKey::Credit(first_id.clone())));
+        assert!(!e.storage().instance().has(&first_id));
+        assert!(e.storage().instance().has(&DataKey::State));
+    });
+}
+
+#[test]
+fn test_credit_readable_after_ledger_advance() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    // Each step stays within the 180 day extension, but together they exceed it
+    let sequence = e.ledger().sequence();
+    e.ledger().set_sequence_number(sequence + 160 * DAY_IN_LEDGERS);
+    client.bump_credit_ttl(&credit_id);
+    e.ledger().set_sequence_number(sequence + 320 * DAY_IN_LEDGERS);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 10 * TON);
+
+    assert_eq!(
+        client.try_bump_credit_ttl(&String::from_str(&e, "missing")),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_transfer_credit_ownership_moves_record_and_tokens() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cooperative = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+    mint_verified(&e, &client, &farmer, "F1", "S2", 10 * TON);
+
+    client.transfer_credit_ownership(&credit_id, &cooperative, &None);
+
+    assert_eq!(last_signer(&e), farmer);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().farmer_address, cooperative);
+    assert_eq!(client.balance(&farmer), 10 * TON);
+    assert_eq!(client.balance(&cooperative), 40 * TON);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &0, &10).len(), 1);
+    assert_eq!(client.list_credits_by_farmer(&cooperative, &0, &10).get_unchecked(0).id, credit_id);
+}
+
+#[test]
+fn test_credit_with_reserved_tons_cannot_be_transferred() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cooperative = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &cooperative, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    client.cancel_order(&order_id, &None);
+    client.transfer_credit_ownership(&credit_id, &cooperative, &None);
+    assert_eq!(client.balance(&cooperative), 40 * TON);
+}
+
+#[test]
+fn test_retired_credit_cannot_be_transferred() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+    client.retire_credits(&credit_id, &farmer, &(40 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &Address::generate(&e), &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_retirement_certificate_matches_retire_call() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let note = Some(String::from_str(&e, "2024 scope 1"));
+
+    e.ledger().set_timestamp(NOW + 60);
+    let first_id = client.retire_credits(&credit_id, &farmer, &(30 * TON), &RetirementReason::ComplianceOffset, &note);
+    let second_id = client.retire_credits(&credit_id, &farmer, &(70 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    let certificate = client.get_retirement_certificate(&first_id).unwrap();
+    assert_eq!(certificate.id, first_id);
+    assert_eq!(certificate.credit_id, credit_id);
+    assert_eq!(certificate.retiree, farmer);
+    assert_eq!(certificate.amount, 30 * TON);
+    assert_eq!(certificate.reason, RetirementReason::ComplianceOffset);
+    assert_eq!(certificate.note, note);
+    assert_eq!(certificate.retired_at, NOW + 60);
+
+    let certificates = client.list_retirements_by_credit(&credit_id);
+    assert_eq!(certificates.len(), 2);
+    assert_eq!(certificates.get_unchecked(0), certificate);
+    assert_eq!(certificates.get_unchecked(1).id, second_id);
+    assert_eq!(client.get_retirement_certificate(&String::from_str(&e, "CERT_missing")), None);
+}
+
+#[test]
+fn test_batch_mint_for_three_farmers() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmers = [Address::generate(&e), Address::generate(&e), Address::generate(&e)];
+    let requests = vec![
+        &e,
+        mint_request(&e, &farmers[0], "F1", "S1", 10 * TON),
+        mint_request(&e, &farmers[0], "F1", "S2", 20 * TON),
+        mint_request(&e, &farmers[1], "F2", "S1", 30 * TON),
+        mint_request(&e, &farmers[2], "F3", "S1", 40 * TON),
+        mint_request(&e, &farmers[2], "F3", "S2", 50 * TON),
+    ];
+
+    let credit_ids = client.batch_mint_carbon_credits(&requests, &Vec::new(&e));
+
+    assert_eq!(credit_ids.len(), 5);
+    for (i, credit_id) in credit_ids.iter().enumerate() {
+        let credit = client.get_carbon_credit(&credit_id).unwrap();
+        assert_eq!(credit.farm_id, requests.get_unchecked(i as u32).farm_id);
+        assert_eq!(credit.season_id, requests.get_unchecked(i as u32).season_id);
+    }
+    let stats = client.get_contract_stats();
+    assert_eq!(stats.farmer_count, 3);
+    assert_eq!(stats.total_credits_minted, 150 * TON);
+    assert_eq!(client.balance(&farmers[2]), 90 * TON);
+}
+
+#[test]
+fn test_batch_mint_is_atomic_and_capped() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+
+    // The second entry repeats the first farm season, so nothing is minted
+    let requests = vec![
+        &e,
+        mint_request(&e, &farmer, "F1", "S1", 10 * TON),
+        mint_request(&e, &farmer, "F1", "S1", 10 * TON),
+    ];
+    assert!(client.try_batch_mint_carbon_credits(&requests, &Vec::new(&e)).is_err());
+    assert_eq!(client.get_credit_count(), 0);
+    assert_eq!(client.balance(&farmer), 0);
+
+    let mut requests = Vec::new(&e);
+    for i in 0..=MAX_BATCH_SIZE {
+        requests.push_back(mint_request(&e, &farmer, "F1", &std::format!("S{i}"), 10 * TON));
+    }
+    assert_eq!(
+        client.try_batch_mint_carbon_credits(&requests, &Vec::new(&e)),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(
+        client.try_batch_mint_carbon_credits(&Vec::new(&e), &Vec::new(&e)),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_farmer_count_counts_each_farmer_once() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    for season in ["S1", "S2", "S3"] {
+        mint_pending(&e, &client, &farmer, "F1", season, 10 * TON);
+    }
+    assert_eq!(client.get_contract_stats().farmer_count, 1);
+
+    mint_pending(&e, &client, &Address::generate(&e), "F2", "S1", 10 * TON);
+    assert_eq!(client.get_contract_stats().farmer_count, 2);
+}
+
+#[test]
+fn test_pending_credit_verified_then_listed() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Pending);
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+
+    // Only registered verifiers may verify
+    assert_eq!(
+        client.try_verify_credit(&verifier, &credit_id, &VerificationLevel::Premium),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    client.add_verifier(&verifier);
+    assert!(client.is_verifier(&verifier));
+    client.verify_credit(&verifier, &credit_id, &VerificationLevel::Premium);
+    assert_eq!(last_signer(&e), verifier);
+
+    let credit = client.get_carbon_credit(&credit_id).unwrap();
+    assert_eq!(credit.status, CreditStatus::Verified);
+    assert_eq!(credit.verification_level, VerificationLevel::Premium);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 10 * TON);
+    assert_eq!(
+        client.try_verify_credit(&verifier, &credit_id, &VerificationLevel::Gold),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_removed_verifier_cannot_verify() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let verifier = Address::generate(&e);
+    let credit_id = mint_pending(&e, &client, &Address::generate(&e), "F1", "S1", 100 * TON);
+    client.add_verifier(&verifier);
+    client.remove_verifier(&verifier);
+
+    assert!(!client.is_verifier(&verifier));
+    assert_eq!(
+        client.try_verify_credit(&verifier, &credit_id, &VerificationLevel::Basic),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_only_verified_credits_can_be_listed() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let pending_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let retired_id = mint_verified(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    client.retire_credits(&retired_id, &farmer, &(10 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(
+        client.try_list_for_sale(&pending_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    assert_eq!(
+        client.try_list_for_sale(&retired_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_listed_credit_cannot_be_listed_again() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None),
+        Err(Ok(CarbonCreditError::OverListed))
+    );
+
+    // Cancelling frees the credit for a new listing
+    client.cancel_order(&order_id, &None);
+    client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+}
+
+#[test]
+fn test_admin_handover_takes_two_steps() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let new_admin = Address::generate(&e);
+
+    assert_eq!(client.try_accept_admin(), Err(Ok(CarbonCreditError::NotAuthorized)));
+    client.propose_admin(&new_admin);
+    assert_eq!(last_signer(&e), admin);
+    let data = event_data(&e, &client, (symbol_short!("admin"), symbol_short!("propose"))).unwrap();
+    assert_eq!(<(Address, Address)>::try_from_val(&e, &data).unwrap(), (admin.clone(), new_admin.clone()));
+    assert_eq!(client.get_contract_stats().admin, admin);
+
+    client.accept_admin();
+    assert_eq!(last_signer(&e), new_admin);
+    let data = event_data(&e, &client, (symbol_short!("admin"), symbol_short!("accept"))).unwrap();
+    assert_eq!(<(Address, Address)>::try_from_val(&e, &data).unwrap(), (admin, new_admin.clone()));
+    assert_eq!(client.get_contract_stats().admin, new_admin);
+    assert_eq!(client.try_accept_admin(), Err(Ok(CarbonCreditError::NotAuthorized)));
+}
+
+#[test]
+fn test_only_pending_admin_can_accept() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let new_admin = Address::generate(&e);
+    let intruder = Address::generate(&e);
+    client.propose_admin(&new_admin);
+
+    e.mock_auths(&[MockAuth {
+        address: &intruder,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "accept_admin",
+            args: ().into_val(&e),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(client.try_accept_admin().is_err());
+    assert_eq!(client.get_contract_stats().admin, admin);
+}
+
+/// Mint a credit from `request`, returning the contract error if it is rejected
+fn try_mint(client: &CarbonCreditTokenClient, request: &MintRequest) -> Result<String, Result<CarbonCreditError, InvokeError>> {
+    client.try_mint_carbon_credit(
+        &request.farmer_address,
+        &request.farm_id,
+        &request.season_id,
+        &request.carbon_amount,
+        &request.verification_level,
+        &request.methodology,
+        &request.vintage,
+        &request.report_hash,
+        &request.coordinates,
+        &request.metadata,
+    ).map(|credit_id| credit_id.unwrap())
+}
+
+#[test]
+fn test_mint_validates_coordinates() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+
+    let mut request = mint_request(&e, &farmer, "F1", "S1", 10 * TON);
+    request.coordinates = vec![&e, -90 * COORDINATE_SCALE, 180 * COORDINATE_SCALE];
+    let credit_id = try_mint(&client, &request).unwrap();
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().coordinates, request.coordinates);
+
+    for coordinates in [
+        vec![&e, 225_000_000],
+        vec![&e, 225_000_000, 806_000_000, 0],
+        vec![&e, 90 * COORDINATE_SCALE + 1, 806_000_000],
+        vec![&e, -90 * COORDINATE_SCALE - 1, 806_000_000],
+        vec![&e, 225_000_000, 180 * COORDINATE_SCALE + 1],
+    ] {
+        let mut request = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+        request.coordinates = coordinates;
+        assert_eq!(try_mint(&client, &request), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    }
+}
+
+#[test]
+fn test_mint_requires_approved_methodology() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let methodology = String::from_str(&e, "VM0042");
+
+    let mut request = mint_request(&e, &farmer, "F1", "S1", 10 * TON);
+    request.methodology = methodology.clone();
+    assert!(!client.is_methodology_approved(&methodology));
+    assert_eq!(try_mint(&client, &request), Err(Ok(CarbonCreditError::InvalidCreditData)));
+
+    client.approve_methodology(&methodology);
+    let credit_id = try_mint(&client, &request).unwrap();
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().methodology, methodology);
+
+    client.revoke_methodology(&methodology);
+    let mut request = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+    request.methodology = methodology;
+    assert_eq!(try_mint(&client, &request), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    assert!(client.is_methodology_approved(&String::from_str(&e, "CDM AMS-III.AU")));
+}
+
+#[test]
+fn test_mint_validates_vintage_year() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let with_vintage = |season: &str, vintage: u32| {
+        let mut request = mint_request(&e, &farmer, "F1", season, 10 * TON);
+        request.vintage = vintage;
+        request
+    };
+
+    // The ledger is in 2025
+    try_mint(&client, &with_vintage("S1", 2025)).unwrap();
+    try_mint(&client, &with_vintage("S2", 2000)).unwrap();
+    assert_eq!(try_mint(&client, &with_vintage("S3", 2026)), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    assert_eq!(try_mint(&client, &with_vintage("S3", 1999)), Err(Ok(CarbonCreditError::InvalidCreditData)));
+
+    client.update_min_vintage(&2020);
+    assert_eq!(try_mint(&client, &with_vintage("S3", 2019)), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    try_mint(&client, &with_vintage("S3", 2020)).unwrap();
+}
+
+#[test]
+fn test_protocol_fee_comes_out_of_seller_share() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let treasury = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.update_fee_settings(&250, &treasury);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(100 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(40 * TON), &i128::MAX);
+
+    // Payment token fees are held for the treasury to withdraw
+    assert_eq!(payment_token.balance(&buyer), 1_000_000_000 - 40_000_000);
+    assert_eq!(payment_token.balance(&farmer), 39_000_000);
+    assert_eq!(payment_token.balance(&client.address), 1_000_000);
+    assert_eq!(client.get_contract_stats().accumulated_fees, 1_000_000);
+    assert_eq!(client.get_contract_stats().total_market_volume, 40_000_000);
+}
+
+#[test]
+fn test_zero_fee_pays_seller_in_full_and_fee_is_capped() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let treasury = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.update_fee_settings(&0, &treasury);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(100 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &(40 * TON), &i128::MAX);
+    assert_eq!(token::Client::new(&e, &payment.address).balance(&farmer), 40_000_000);
+    assert_eq!(client.get_contract_stats().accumulated_fees, 0);
+
+    client.update_fee_settings(&1_000, &treasury);
+    assert_eq!(
+        client.try_update_fee_settings(&1_001, &treasury),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(client.get_contract_stats().fee_bps, 1_000);
+}
+
+#[test]
+fn test_active_orders_exclude_filled_and_cancelled() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let filled = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let cancelled = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let first_active = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let second_active = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    assert_eq!(client.list_active_orders(&0, &10).len(), 4);
+
+    client.buy_carbon_credits(&filled, &buyer, &(10 * TON), &i128::MAX);
+    client.cancel_order(&cancelled, &None);
+
+    // Removed orders are replaced by the last active one, so the order is not kept
+    let active = client.list_active_orders(&0, &10);
+    assert_eq!(active.len(), 2);
+    assert!(active.iter().any(|order| order.id == first_active));
+    assert!(active.iter().any(|order| order.id == second_active));
+    assert_eq!(client.list_active_orders(&1, &10).len(), 1);
+    assert_eq!(client.list_active_orders(&2, &10).len(), 0);
+
+    let order = client.get_market_order(&filled).unwrap();
+    assert_eq!(order.status, String::from_str(&e, "Filled"));
+    assert_eq!(order.seller_address, farmer);
+    assert_eq!(client.get_market_order(&String::from_str(&e, "ORDER_missing")), None);
+}
+
+#[test]
+fn test_seller_cannot_buy_own_order() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    payment.mint(&farmer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &farmer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::SelfTradeNotAllowed))
+    );
+    assert_eq!(client.get_contract_stats().total_market_volume, 0);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 10 * TON);
+}
+
+#[test]
+fn test_zero_or_negative_price_cannot_be_listed() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    for price in [0, -1_000_000] {
+        assert_eq!(
+            client.try_list_for_sale(&credit_id, &price, &(10 * TON), &None, &None, &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+    }
+    assert_eq!(client.balance(&farmer), 100 * TON);
+}
+
+#[test]
+fn test_zero_amount_cannot_be_bought() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    for amount in [0, -TON] {
+        assert_eq!(
+            client.try_buy_carbon_credits(&order_id, &buyer, &amount, &i128::MAX),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+    }
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 10 * TON);
+}
+
+#[test]
+fn test_price_history_records_each_trade_in_order() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(30 * TON), &None, &None, &None);
+
+    for (i, price) in [1_000_000, 2_000_000, 3_000_000].into_iter().enumerate() {
+        e.ledger().set_timestamp(NOW + i as u64 * 60);
+        client.update_order_price(&order_id, &price, &None);
+        client.buy_carbon_credits(&order_id, &buyer, &((i as i128 + 1) * TON), &i128::MAX);
+    }
+
+    let history = client.get_price_history(&credit_id, &0, &10);
+    assert_eq!(history.len(), 3);
+    for (i, point) in history.iter().enumerate() {
+        assert_eq!(point.price_per_ton, (i as i128 + 1) * 1_000_000);
+        assert_eq!(point.amount, (i as i128 + 1) * TON);
+        assert_eq!(point.timestamp, NOW + i as u64 * 60);
+    }
+    assert_eq!(client.get_price_history(&credit_id, &2, &10).get_unchecked(0).price_per_ton, 3_000_000);
+    assert_eq!(client.get_price_history(&credit_id, &3, &10).len(), 0);
+}
+
+#[test]
+fn test_price_history_rolls_off_oldest_trades() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 1_000 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(1_000 * TON), &None, &None, &None);
+
+    for i in 0..=MAX_PRICE_HISTORY {
+        client.update_order_price(&order_id, &(1_000_000 + i as i128), &None);
+        client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    }
+
+    let oldest = client.get_price_history(&credit_id, &0, &1).get_unchecked(0);
+    assert_eq!(oldest.price_per_ton, 1_000_001);
+    let newest = client.get_price_history(&credit_id, &(MAX_PRICE_HISTORY - 1), &1).get_unchecked(0);
+    assert_eq!(newest.price_per_ton, 1_000_000 + MAX_PRICE_HISTORY as i128);
+    assert_eq!(client.get_price_history(&credit_id, &MAX_PRICE_HISTORY, &1).len(), 0);
+}
+
+#[test]
+fn test_auction_price_decays_to_floor() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    let order_id = client.list_for_auction(&credit_id, &10_000_000, &2_000_000, &1_000, &(10 * TON), &None);
+    assert_eq!(client.current_auction_price(&order_id), Some(10_000_000));
+    e.ledger().set_timestamp(NOW + 500);
+    assert_eq!(client.current_auction_price(&order_id), Some(6_000_000));
+    e.ledger().set_timestamp(NOW + 1_000);
+    assert_eq!(client.current_auction_price(&order_id), Some(2_000_000));
+    e.ledger().set_timestamp(NOW + 100_000);
+    assert_eq!(client.current_auction_price(&order_id), Some(2_000_000));
+
+    let fixed_id = client.list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None);
+    assert_eq!(client.current_auction_price(&fixed_id), None);
+    assert_eq!(
+        client.try_list_for_auction(&credit_id, &1_000_000, &2_000_000, &1_000, &TON, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_auction_purchase_charges_decayed_price() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_auction(&credit_id, &10_000_000, &2_000_000, &1_000, &(10 * TON), &None);
+
+    e.ledger().set_timestamp(NOW + 250);
+    client.buy_carbon_credits(&order_id, &buyer, &(2 * TON), &i128::MAX);
+
+    assert_eq!(token::Client::new(&e, &payment.address).balance(&farmer), 16_000_000);
+    assert_eq!(client.get_order_fill(&order_id).unwrap().last_price_per_ton, 8_000_000);
+}
+
+#[test]
+fn test_split_credit_keeps_shared_metadata() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.set_credit_metadata_value(&farmer, &credit_id, &String::from_str(&e, "crop"), &String::from_str(&e, "rice"));
+
+    let split_id = client.split_credit(&credit_id, &(30 * TON), &None);
+
+    assert_eq!(last_signer(&e), farmer);
+    let original = client.get_carbon_credit(&credit_id).unwrap();
+    let split = client.get_carbon_credit(&split_id).unwrap();
+    assert_eq!(original.carbon_amount, 70 * TON);
+    assert_eq!(split.carbon_amount, 30 * TON);
+    assert_eq!(split.farmer_address, farmer);
+    assert_eq!(split.vintage, original.vintage);
+    assert_eq!(split.methodology, original.methodology);
+    assert_eq!(split.metadata, original.metadata);
+    assert_eq!(split.status, CreditStatus::Verified);
+    assert_eq!(client.balance(&farmer), 100 * TON);
+    assert_eq!(client.list_credits_by_farmer(&farmer, &0, &10).len(), 2);
+}
+
+#[test]
+fn test_split_amount_must_leave_both_parts_nonzero() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    for amount in [0, 100 * TON, 101 * TON] {
+        assert_eq!(
+            client.try_split_credit(&credit_id, &amount, &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+    }
+}
+
+#[test]
+fn test_merge_credits_of_same_vintage() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_verified(&e, &client, &farmer, "F1", "S2", 20 * TON);
+    let third_id = mint_verified(&e, &client, &farmer, "F1", "S3", 30 * TON);
+
+    let merged_id = client.merge_credits(&vec![&e, first_id.clone(), second_id.clone(), third_id.clone()], &None);
+
+    assert_eq!(last_signer(&e), farmer);
+    let merged = client.get_carbon_credit(&merged_id).unwrap();
+    assert_eq!(merged.carbon_amount, 60 * TON);
+    assert_eq!(merged.vintage, 2023);
+    assert_eq!(merged.status, CreditStatus::Verified);
+    for credit_id in [first_id, second_id, third_id] {
+        assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Merged);
+    }
+    let held = client.list_credits_by_farmer(&farmer, &0, &10);
+    assert_eq!(held.len(), 1);
+    assert_eq!(held.get_unchecked(0).id, merged_id);
+    assert_eq!(client.balance(&farmer), 60 * TON);
+}
+
+#[test]
+fn test_merge_rejects_mixed_vintages() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let mut request = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+    request.vintage = 2024;
+    let second_id = try_mint(&client, &request).unwrap();
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    client.verify_credit(&verifier, &second_id, &VerificationLevel::Basic);
+
+    assert_eq!(
+        client.try_merge_credits(&vec![&e, first_id.clone(), second_id], &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(
+        client.try_merge_credits(&vec![&e, first_id.clone(), first_id.clone()], &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(client.get_carbon_credit(&first_id).unwrap().status, CreditStatus::Verified);
+}
+
+#[test]
+fn test_credit_status_transitions_are_enforced() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let other_id = mint_verified(&e, &client, &farmer, "F1", "S2", 10 * TON);
+
+    // Pending credits may be retired but a retired credit can never be verified
+    client.retire_credits(&credit_id, &farmer, &(10 * TON), &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().status, CreditStatus::Retired);
+    assert_eq!(
+        client.try_verify_credit(&verifier, &credit_id, &VerificationLevel::Basic),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    // Merged credits live on only through the credit they were merged into
+    let split_id = client.split_credit(&other_id, &(4 * TON), &None);
+    client.merge_credits(&vec![&e, other_id.clone(), split_id], &None);
+    assert_eq!(
+        client.try_retire_credits(&other_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(
+        client.try_list_for_sale(&other_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_migrate_statuses_types_string_statuses() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let current_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let legacy_id = String::from_str(&e, "F1_S0_2023_1");
+    let legacy = StringStatusCarbonCredit {
+        id: legacy_id.clone(),
+        farmer_address: farmer.clone(),
+        farm_id: String::from_str(&e, "F1"),
+        season_id: String::from_str(&e, "S0"),
+        carbon_amount: 10 * TON,
+        verification_level: VerificationLevel::Standard,
+        methodology: String::from_str(&e, "IPCC 2019"),
+        vintage: 2023,
+        report_hash: report_hash(&e, "F1S0"),
+        created_at: NOW,
+        status: String::from_str(&e, "Verified"),
+        coordinates: vec![&e, 225_000_000, 806_000_000],
+        metadata: Map::new(&e),
+    };
+    e.as_contract(&client.address, || {
+        e.storage().persistent().set(&DataKey::Credit(legacy_id.clone()), &legacy)
+    });
+
+    assert_eq!(client.migrate_statuses(&vec![&e, legacy_id.clone(), current_id.clone()]), 1);
+    let migrated = client.get_carbon_credit(&legacy_id).unwrap();
+    assert_eq!(migrated.status, CreditStatus::Verified);
+    assert_eq!(migrated.verification_level, VerificationLevel::Standard);
+    assert_eq!(client.get_carbon_credit(&current_id).unwrap().status, CreditStatus::Pending);
+    assert_eq!(client.migrate_statuses(&vec![&e, legacy_id]), 0);
+}
+
+#[test]
+fn test_credit_expires_after_validity_years() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    assert!(!client.is_credit_expired(&credit_id));
+
+    // A 2023 vintage stays valid through 2025 with two validity years
+    client.update_validity_years(&2);
+    assert!(!client.is_credit_expired(&credit_id));
+    e.ledger().set_timestamp(NOW + 365 * 86_400);
+    assert!(client.is_credit_expired(&credit_id));
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::CreditExpired))
+    );
+}
+
+#[test]
+fn test_expired_credit_can_be_retired_but_not_traded() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    client.update_validity_years(&1);
+    assert!(client.is_credit_expired(&credit_id));
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::CreditExpired))
+    );
+    client.retire_credits(&credit_id, &farmer, &(90 * TON), &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 10 * TON);
+}
+
+#[test]
+fn test_reconcile_supply_after_partial_retirement() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.retire_credits(&credit_id, &farmer, &(30 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    let stats = client.get_contract_stats();
+    assert_eq!(stats.total_tokens_burned, 30 * TON);
+    assert_eq!(stats.total_credits_retired, 30 * TON);
+    assert_eq!(client.reconcile_supply(), (70 * TON, 70 * TON));
+    assert_eq!(client.total_supply(), 70 * TON);
+}
+
+#[test]
+fn test_buyer_kyc_gate() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let verified_buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    payment.mint(&verified_buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    // Anyone may buy while KYC is off
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+
+    client.update_buyer_kyc(&true);
+    client.add_to_allowlist(&admin, &verified_buyer);
+    assert!(client.is_allowlisted(&verified_buyer));
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    client.buy_carbon_credits(&order_id, &verified_buyer, &TON, &i128::MAX);
+
+    client.remove_from_allowlist(&admin, &verified_buyer);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &verified_buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_add_to_allowlist(&buyer, &buyer),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_blocked_farmer_cannot_mint_or_list() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.block_address(&farmer);
+    assert!(client.is_blocked(&farmer));
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 10 * TON)),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert!(client.try_transfer(&farmer, &Address::generate(&e), &TON).is_err());
+
+    client.unblock_address(&farmer);
+    client.list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None);
+}
+
+#[test]
+fn test_blocked_buyer_cannot_purchase() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    client.block_address(&buyer);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert!(client.try_transfer(&farmer, &buyer, &TON).is_err());
+    assert_eq!(client.balance(&buyer), 0);
+}
+
+#[test]
+fn test_blocked_parties_cannot_transfer_credit_ownership() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cooperative = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+
+    client.block_address(&farmer);
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &cooperative, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+
+    client.unblock_address(&farmer);
+    client.block_address(&cooperative);
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &cooperative, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().farmer_address, farmer);
+    assert_eq!(client.balance(&farmer), 40 * TON);
+    assert_eq!(client.balance(&cooperative), 0);
+}
+
+#[test]
+fn test_report_hash_backs_only_one_credit() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first = mint_request(&e, &farmer, "F1", "S1", 10 * TON);
+    let mut reused = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+    reused.report_hash = first.report_hash.clone();
+
+    try_mint(&client, &first).unwrap();
+    assert_eq!(try_mint(&client, &reused), Err(Ok(CarbonCreditError::CreditAlreadyExists)));
+    try_mint(&client, &mint_request(&e, &farmer, "F1", "S3", 10 * TON)).unwrap();
+    assert_eq!(client.get_credit_count(), 2);
+
+    // The admin may release a hash, e.g. after the first credit's report was corrected
+    client.invalidate_report_hash(&first.report_hash);
+    try_mint(&client, &reused).unwrap();
+    assert_eq!(
+        client.try_invalidate_report_hash(&report_hash(&e, "unused")),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_catalog_filters_by_vintage_and_methodology() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cdm = String::from_str(&e, "CDM AMS-III.AU");
+    let ipcc = String::from_str(&e, "IPCC 2019");
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_verified(&e, &client, &farmer, "F1", "S2", 20 * TON);
+    let mut request = mint_request(&e, &farmer, "F2", "S1", 30 * TON);
+    request.vintage = 2022;
+    request.methodology = cdm.clone();
+    let older_id = try_mint(&client, &request).unwrap();
+
+    let ids = |credits: Vec<CarbonCredit>| -> std::vec::Vec<String> { credits.iter().map(|c| c.id).collect() };
+    assert_eq!(ids(client.list_credits_by_vintage(&2023, &0, &10)), [first_id.clone(), second_id.clone()]);
+    assert_eq!(ids(client.list_credits_by_vintage(&2022, &0, &10)), std::vec![older_id.clone()]);
+    assert_eq!(ids(client.list_credits_by_vintage(&2023, &1, &10)), std::vec![second_id.clone()]);
+    assert_eq!(client.list_credits_by_vintage(&2021, &0, &10).len(), 0);
+    assert_eq!(ids(client.list_credits_by_methodology(&ipcc, &0, &10)), [first_id.clone(), second_id.clone()]);
+    assert_eq!(ids(client.list_credits_by_methodology(&cdm, &0, &10)), std::vec![older_id.clone()]);
+
+    // Merged and fully retired credits drop out, the merged credit takes their place
+    let merged_id = client.merge_credits(&vec![&e, first_id, second_id], &None);
+    client.retire_credits(&older_id, &farmer, &(30 * TON), &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(ids(client.list_credits_by_vintage(&2023, &0, &10)), std::vec![merged_id.clone()]);
+    assert_eq!(ids(client.list_credits_by_methodology(&ipcc, &0, &10)), [merged_id]);
+    assert_eq!(client.list_credits_by_vintage(&2022, &0, &10).len(), 0);
+    assert_eq!(client.list_credits_by_methodology(&cdm, &0, &10).len(), 0);
+}
+
+#[test]
+fn test_impact_summary_after_mints_trades_and_retirement() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let first_farmer = Address::generate(&e);
+    let second_farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let empty = client.get_impact_summary();
+    assert_eq!((empty.total_minted, empty.farmer_count, empty.average_price), (0, 0, 0));
+
+    let first_id = mint_verified(&e, &client, &first_farmer, "F1", "S1", 100 * TON);
+    let second_id = mint_verified(&e, &client, &second_farmer, "F2", "S1", 50 * TON);
+    mint_verified(&e, &client, &first_farmer, "F1", "S2", 20 * TON);
+    let first_order = client.list_for_sale(&first_id, &2_000_000, &(40 * TON), &None, &None, &None);
+    let second_order = client.list_for_sale(&second_id, &4_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&first_order, &buyer, &(10 * TON), &i128::MAX);
+    client.buy_carbon_credits(&second_order, &buyer, &(10 * TON), &i128::MAX);
+    let bought_id = client.list_credits_by_farmer(&buyer, &0, &1).get_unchecked(0).id;
+    client.retire_credits(&bought_id, &buyer, &(10 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(
+        client.get_impact_summary(),
+        ImpactSummary {
+            total_minted: 170 * TON,
+            total_retired: 10 * TON,
+            // 30 tons are still listed on the first order
+            total_active: 130 * TON,
+            farmer_count: 2,
+            total_market_volume: 60_000_000,
+            average_price: 3_000_000,
+        }
+    );
+}
+
+#[test]
+fn test_settled_escrow_pays_seller() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    payment.mint(&buyer, &100_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+
+    let escrow_id = client.create_escrow_order(&order_id, &buyer, &(4 * TON));
+    assert_eq!(payment_token.balance(&buyer), 92_000_000);
+    assert_eq!(payment_token.balance(&client.address), 8_000_000);
+    assert_eq!(payment_token.balance(&farmer), 0);
+
+    let outsider = Address::generate(&e);
+    assert_eq!(client.try_settle_escrow(&outsider, &escrow_id), Err(Ok(CarbonCreditError::NotAuthorized)));
+    client.add_verifier(&verifier);
+    client.settle_escrow(&verifier, &escrow_id);
+
+    assert_eq!(client.get_escrow(&escrow_id).unwrap().status, String::from_str(&e, "Settled"));
+    assert_eq!(payment_token.balance(&farmer), 8_000_000);
+    assert_eq!(payment_token.balance(&client.address), 0);
+    assert_eq!(client.balance(&buyer), 4 * TON);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 6 * TON);
+    assert_eq!(client.try_refund_escrow(&verifier, &escrow_id), Err(Ok(CarbonCreditError::InvalidCreditData)));
+}
+
+#[test]
+fn test_refunded_escrow_returns_payment_and_credits() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &100_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+
+    // While the order is active the tons go back on it
+    let escrow_id = client.create_escrow_order(&order_id, &buyer, &(4 * TON));
+    client.refund_escrow(&admin, &escrow_id);
+    assert_eq!(client.get_escrow(&escrow_id).unwrap().status, String::from_str(&e, "Refunded"));
+    assert_eq!(payment_token.balance(&buyer), 100_000_000);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 10 * TON);
+    assert_eq!(client.try_settle_escrow(&admin, &escrow_id), Err(Ok(CarbonCreditError::InvalidCreditData)));
+
+    // Once the order is cancelled they go back to the seller
+    let escrow_id = client.create_escrow_order(&order_id, &buyer, &(4 * TON));
+    client.cancel_order(&order_id, &None);
+    assert_eq!(client.balance(&farmer), 96 * TON);
+    client.refund_escrow(&admin, &escrow_id);
+    assert_eq!(payment_token.balance(&buyer), 100_000_000);
+    assert_eq!(client.balance(&farmer), 100 * TON);
+    assert_eq!(client.balance(&buyer), 0);
+    assert_eq!(client.get_reserved_amount(&credit_id), 0);
+}
+
+#[test]
+fn test_royalty_paid_on_resale_only() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let reseller = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&reseller, &100_000_000);
+    payment.mint(&buyer, &100_000_000);
+    assert_eq!(client.try_update_royalty_settings(&1_001), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    client.update_royalty_settings(&500);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    // Primary sale: the farmer is the seller, so no royalty is due
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &reseller, &(10 * TON), &i128::MAX);
+    assert_eq!(payment_token.balance(&farmer), 20_000_000);
+
+    // Secondary sale: 5% of the 40 paid goes to the original farmer
+    let bought_id = client.list_credits_by_farmer(&reseller, &0, &1).get_unchecked(0).id;
+    assert_eq!(client.get_carbon_credit(&bought_id).unwrap().royalty_bps, 500);
+    let resale_id = client.list_for_sale(&bought_id, &4_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&resale_id, &buyer, &(10 * TON), &i128::MAX);
+    assert_eq!(payment_token.balance(&farmer), 22_000_000);
+    assert_eq!(payment_token.balance(&reseller), 80_000_000 + 38_000_000);
+    assert_eq!(payment_token.balance(&buyer), 60_000_000);
+}
+
+/// Overwrite the contract state in storage
+fn edit_state(e: &Env, client: &CarbonCreditTokenClient, edit: impl FnOnce(&mut ContractState)) {
+    e.as_contract(&client.address, || {
+        let mut state: ContractState = e.storage().instance().get(&DataKey::State).unwrap();
+        edit(&mut state);
+        e.storage().instance().set(&DataKey::State, &state);
+    });
+}
+
+#[test]
+fn test_totals_near_i128_max_fail_with_overflow() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    // Shift minted and retired alike so the supply check still holds
+    edit_state(&e, &client, |state| {
+        state.total_credits_minted += i128::MAX - 150 * TON;
+        state.total_credits_retired += i128::MAX - 150 * TON;
+    });
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 100 * TON)),
+        Err(Ok(CarbonCreditError::ArithmeticOverflow))
+    );
+    edit_state(&e, &client, |state| state.total_credits_retired = i128::MAX - TON / 2);
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::ArithmeticOverflow))
+    );
+    edit_state(&e, &client, |state| state.total_market_volume = i128::MAX - 1);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::ArithmeticOverflow))
+    );
+}
+
+#[test]
+fn test_price_times_amount_overflow_is_rejected() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &(i128::MAX / 2), &(10 * TON), &None, &None, &None);
+
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &(10 * TON), &i128::MAX),
+        Err(Ok(CarbonCreditError::ArithmeticOverflow))
+    );
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 10 * TON);
+}
+
+#[test]
+fn test_frozen_credit_blocks_trading_until_unfrozen() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let new_owner = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    let outsider = Address::generate(&e);
+    assert_eq!(client.try_freeze_credit(&outsider, &credit_id), Err(Ok(CarbonCreditError::NotAuthorized)));
+    client.freeze_credit(&admin, &credit_id);
+    assert!(client.is_credit_frozen(&credit_id));
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &new_owner, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+
+    client.unfreeze_credit(&admin, &credit_id);
+    assert!(!client.is_credit_frozen(&credit_id));
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+    client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.cancel_order(&order_id, &None);
+}
+
+#[test]
+fn test_partial_listing_escrows_only_listed_tons() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(2 * TON), &None, &None, &None);
+
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 2 * TON);
+    assert_eq!(client.balance(&farmer), 8 * TON);
+    assert_eq!(client.get_reserved_amount(&credit_id), 2 * TON);
+    assert_eq!(client.get_available_amount(&credit_id), 8 * TON);
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &0, &None, &None, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(8 * TON + 1), &None, &None, &None),
+        Err(Ok(CarbonCreditError::OverListed))
+    );
+}
+
+#[test]
+fn test_paused_contract_blocks_mutations_but_not_views() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    client.pause_contract();
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 100 * TON)),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::ContractPaused))
+    );
+    let stats = client.get_contract_stats();
+    assert!(stats.contract_paused);
+    assert!(stats.market_open);
+    assert_eq!(stats.total_credits_minted, 100 * TON);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON);
+
+    client.unpause_contract();
+    mint_pending(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+}
+
+#[test]
+fn test_only_admin_may_upgrade() {
+    let e = Env::default();
+    let (client, _, _) = setup(&e);
+    let outsider = Address::generate(&e);
+    let wasm_hash = BytesN::from_array(&e, &[7; 32]);
+    assert_eq!(client.version(), 1);
+
+    e.mock_auths(&[MockAuth {
+        address: &outsider,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "upgrade",
+            args: (wasm_hash.clone(),).into_val(&e),
+            sub_invokes: &[],
+        },
+    }]);
+    assert!(matches!(client.try_upgrade(&wasm_hash), Err(Err(_))));
+    assert_eq!(client.version(), 1);
+}
+
+/// Fields `ContractState` gained after schema version 2
+const FIELDS_SINCE_SCHEMA_V2: [&str; 12] = [
+    "min_mint_amount",
+    "max_mint_amount",
+    "amount_unit",
+    "max_price_deviation_bps",
+    "verifier_pubkey",
+    "accumulated_fees",
+    "rounding_mode",
+    "tokens_frozen",
+    "credit_nonce",
+    "list_fee",
+    "mint_rate_limit",
+    "mint_rate_window",
+];
+
+/// Rewrite the stored state without `dropped` fields, as an older layout stored it
+fn store_older_layout(e: &Env, client: &CarbonCreditTokenClient, dropped: &[&str], schema_version: Option<u32>) {
+    e.as_contract(&client.address, || {
+        let mut raw: Map<Symbol, Val> = e.storage().instance().get(&DataKey::State).unwrap();
+        for name in dropped {
+            raw.remove(Symbol::new(e, name));
+        }
+        match schema_version {
+            Some(version) => raw.set(Symbol::new(e, "schema_version"), version.into_val(e)),
+            None => {
+                raw.remove(Symbol::new(e, "schema_version"));
+            }
+        }
+        e.storage().instance().set(&DataKey::State, &raw);
+    });
+}
+
+#[test]
+fn test_migrate_legacy_state_keeps_counters_and_runs_once() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.retire_credits(&credit_id, &farmer, &(30 * TON), &RetirementReason::VoluntaryOffset, &None);
+    client.update_fee_settings(&250, &admin);
+
+    // The layout in use before the state was versioned
+    store_older_layout(&e, &client, &FIELDS_SINCE_SCHEMA_V2, None);
+
+    assert_eq!(client.migrate(&None), SCHEMA_VERSION);
+    assert_eq!(last_signer(&e), admin);
+    let after = client.get_contract_stats();
+    assert_eq!(after.schema_version, SCHEMA_VERSION);
+    assert_eq!(after.total_credits_minted, 100 * TON);
+    assert_eq!(after.total_credits_retired, 30 * TON);
+    assert_eq!(after.total_tokens_burned, 30 * TON);
+    assert_eq!(after.farmer_count, 1);
+    assert_eq!(after.fee_bps, 250);
+    assert_eq!(after.min_mint_amount, TON);
+    assert_eq!(after.mint_rate_window, 0);
+    assert_eq!(client.try_migrate(&None), Err(Ok(CarbonCreditError::InvalidCreditData)));
+
+    // An earlier unversioned layout gets the fields it predates as well
+    store_older_layout(&e, &client, &["royalty_bps", "contract_paused", "version"], None);
+    assert_eq!(client.migrate(&None), SCHEMA_VERSION);
+    let after = client.get_contract_stats();
+    assert_eq!((after.royalty_bps, after.contract_paused, after.version), (0, false, 1));
+    assert_eq!(after.fee_bps, 250);
+}
+
+#[test]
+fn test_migrate_steps_through_every_later_layout() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.update_fee_settings(&250, &admin);
+
+    // Each versioned layout lacks the fields added after it
+    for (version, dropped) in [(2, 12), (3, 10), (5, 8), (8, 4), (11, 2)] {
+        store_older_layout(&e, &client, &FIELDS_SINCE_SCHEMA_V2[12 - dropped..], Some(version));
+        assert_eq!(client.migrate(&None), SCHEMA_VERSION);
+        let after = client.get_contract_stats();
+        assert_eq!(after.schema_version, SCHEMA_VERSION);
+        assert_eq!(after.total_credits_minted, 100 * TON);
+        assert_eq!(after.fee_bps, 250);
+        assert_eq!(after.amount_unit, 1);
+        assert_eq!(after.rounding_mode, RoundingMode::Floor);
+        assert_eq!(after.mint_rate_limit, 0);
+    }
+
+    // A layout claiming a version whose fields it lacks cannot be decoded
+    store_older_layout(&e, &client, &["list_fee"], Some(11));
+    assert!(client.try_migrate(&None).is_err());
+}
+
+#[test]
+fn test_migrate_original_layout_needs_a_payment_token() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+
+    // The layout the contract first shipped with
+    e.as_contract(&client.address, || {
+        let mut raw: Map<Symbol, Val> = Map::new(&e);
+        raw.set(Symbol::new(&e, "total_credits_minted"), 0i128.into_val(&e));
+        raw.set(Symbol::new(&e, "total_credits_retired"), 0i128.into_val(&e));
+        raw.set(Symbol::new(&e, "total_market_volume"), 5_000_000i128.into_val(&e));
+        raw.set(Symbol::new(&e, "farmer_count"), 3u32.into_val(&e));
+        raw.set(Symbol::new(&e, "admin"), admin.into_val(&e));
+        raw.set(Symbol::new(&e, "market_open"), true.into_val(&e));
+        raw.set(Symbol::new(&e, "min_verification_level"), String::from_str(&e, "Standard").into_val(&e));
+        e.storage().instance().set(&DataKey::State, &raw);
+    });
+
+    assert_eq!(client.try_migrate(&None), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    assert_eq!(client.migrate(&Some(payment.address.clone())), SCHEMA_VERSION);
+    let after = client.get_contract_stats();
+    assert_eq!(after.payment_token, payment.address);
+    assert_eq!(after.min_verification_level, VerificationLevel::Standard);
+    assert_eq!(after.total_market_volume, 5_000_000);
+    assert_eq!(after.farmer_count, 3);
+    assert_eq!(after.treasury, admin);
+    assert_eq!(after.version, 1);
+    assert_eq!(after.ttl_extend_to, 180 * DAY_IN_LEDGERS);
+
+    // The migrated contract is fully usable
+    mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    assert_eq!(client.balance(&farmer), 10 * TON);
+}
+
+#[test]
+fn test_farmer_earnings_sum_net_proceeds_of_sales() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_fee_settings(&1_000, &admin);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let second_id = mint_verified(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    assert_eq!(client.get_farmer_earnings(&farmer), 0);
+
+    let first_order = client.list_for_sale(&first_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    let second_order = client.list_for_sale(&second_id, &3_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&first_order, &buyer, &(10 * TON), &i128::MAX);
+    client.buy_carbon_credits(&second_order, &buyer, &(5 * TON), &i128::MAX);
+
+    // 20 and 15 paid, less the 10% protocol fee
+    assert_eq!(client.get_farmer_earnings(&farmer), 18_000_000 + 13_500_000);
+    assert_eq!(client.get_farmer_earnings(&buyer), 0);
+}
+
+#[test]
+fn test_mint_amount_bounds() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let mint = |season: &str, amount: i128| try_mint(&client, &mint_request(&e, &farmer, "F1", season, amount));
+
+    assert_eq!(mint("S1", TON - 1), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    assert_eq!(mint("S1", 10_000 * TON + 1), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    mint("S1", TON).unwrap();
+    mint("S2", 10_000 * TON).unwrap();
+
+    assert_eq!(client.try_update_mint_bounds(&(5 * TON), &TON), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    client.update_mint_bounds(&(5 * TON), &(50 * TON));
+    assert_eq!(mint("S3", 4 * TON), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    assert_eq!(mint("S3", 51 * TON), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    mint("S3", 50 * TON).unwrap();
+}
+
+#[test]
+fn test_mint_validates_report_hash_format() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+
+    let cid_v0 = "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbdG";
+    let cid_v1 = "bafybeigdyrzt5sfp7udm7hu76uh7y26nf3efuylqabf3oclgtqy55fbzdi";
+    let mut request = mint_request(&e, &farmer, "F1", "S1", 10 * TON);
+    request.report_hash = String::from_str(&e, cid_v0);
+    try_mint(&client, &request).unwrap();
+    let mut request = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+    request.report_hash = String::from_str(&e, cid_v1);
+    try_mint(&client, &request).unwrap();
+
+    for hash in [
+        "",
+        "not-a-cid",
+        // CIDv0 one character short, and with a character outside base58
+        "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbd",
+        "QmYwAPJzv5CZsnA625s3Xf2nemtYgPpHdWEz79ojWnPbd0",
+        // CIDv1 with uppercase base32
+        "bafyBEIGDYRZT5SFP7UDM7HU76UH7Y26NF3EFUYLQABF3OCLGTQY55FBZDI",
+    ] {
+        let mut request = mint_request(&e, &farmer, "F1", "S3", 10 * TON);
+        request.report_hash = String::from_str(&e, hash);
+        assert_eq!(try_mint(&client, &request), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    }
+}
+
+#[test]
+fn test_circulating_credits_match_accounting_and_flag_drift() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.retire_credits(&credit_id, &farmer, &(30 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(client.circulating_credits(), 70 * TON);
+    assert!(event_data(&e, &client, (symbol_short!("supply"), symbol_short!("drift"))).is_none());
+
+    edit_state(&e, &client, |state| state.total_credits_retired -= TON);
+    assert_eq!(client.circulating_credits(), 70 * TON);
+    let data = event_data(&e, &client, (symbol_short!("supply"), symbol_short!("drift"))).unwrap();
+    assert_eq!(<(i128, i128)>::try_from_val(&e, &data).unwrap(), (70 * TON, 71 * TON));
+}
+
+#[test]
+fn test_batch_retirement_across_three_credits() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let mut retirements = Vec::new(&e);
+    for (season, amount) in [("S1", 10 * TON), ("S2", 20 * TON), ("S3", 30 * TON)] {
+        let credit_id = mint_verified(&e, &client, &farmer, "F1", season, 100 * TON);
+        retirements.push_back(RetireRequest { credit_id, amount });
+    }
+    let note = Some(String::from_str(&e, "FY2024 offsets"));
+
+    let aggregate_id = client.retire_credits_batch(&farmer, &retirements, &RetirementReason::ComplianceOffset, &note);
+
+    let aggregate = client.get_aggregate_certificate(&aggregate_id).unwrap();
+    assert_eq!(aggregate.retiree, farmer);
+    assert_eq!(aggregate.total_amount, 60 * TON);
+    assert_eq!(aggregate.note, note);
+    assert_eq!(aggregate.certificate_ids.len(), 3);
+    for (certificate_id, request) in aggregate.certificate_ids.iter().zip(retirements.iter()) {
+        let certificate = client.get_retirement_certificate(&certificate_id).unwrap();
+        assert_eq!(certificate.credit_id, request.credit_id);
+        assert_eq!(certificate.amount, request.amount);
+    }
+    assert_eq!(client.get_contract_stats().total_tokens_burned, 60 * TON);
+    assert_eq!(client.balance(&farmer), 240 * TON);
+
+    // One bad entry rolls back the whole batch
+    retirements.push_back(RetireRequest { credit_id: retirements.get_unchecked(0).credit_id, amount: 91 * TON });
+    assert_eq!(
+        client.try_retire_credits_batch(&farmer, &retirements, &RetirementReason::ComplianceOffset, &None),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+    assert_eq!(client.balance(&farmer), 240 * TON);
+}
+
+#[test]
+fn test_region_stats_per_one_degree_cell() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let mint_at = |season: &str, latitude: i128, longitude: i128, amount: i128| {
+        let mut request = mint_request(&e, &farmer, "F1", season, amount);
+        request.coordinates = vec![&e, latitude, longitude];
+        try_mint(&client, &request).unwrap();
+    };
+
+    mint_at("S1", 225_000_000, 806_000_000, 10 * TON);
+    mint_at("S2", 229_999_999, 800_000_000, 20 * TON);
+    // Southern and western coordinates round down to the cell below
+    mint_at("S3", -123_000_000, -450_000_000, 30 * TON);
+
+    let mandla = String::from_str(&e, "22_80");
+    let southwest = String::from_str(&e, "-13_-45");
+    assert_eq!(client.get_region_stats(&mandla), 30 * TON);
+    assert_eq!(client.get_region_stats(&southwest), 30 * TON);
+    assert_eq!(client.get_region_stats(&String::from_str(&e, "23_80")), 0);
+    assert_eq!(client.list_regions(), vec![&e, mandla, southwest]);
+}
+
+#[test]
+fn test_amounts_must_align_with_amount_unit() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    assert_eq!(client.try_update_amount_unit(&0), Err(Ok(CarbonCreditError::InvalidCreditData)));
+
+    // Retire in whole kilograms only
+    client.update_amount_unit(&1_000);
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &1_500_500, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    client.retire_credits(&credit_id, &farmer, &1_500_000, &RetirementReason::VoluntaryOffset, &None);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON - 1_500_000);
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 10 * TON + 1)),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    // Splits carve off whole kilograms as well
+    assert_eq!(
+        client.try_split_credit(&credit_id, &(TON + 1), &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON - 1_500_000);
+    let split_id = client.split_credit(&credit_id, &(TON + 1_000), &None);
+    assert_eq!(client.get_carbon_credit(&split_id).unwrap().carbon_amount, TON + 1_000);
+}
+
+#[test]
+fn test_locked_credit_tradable_only_after_unlock() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let new_owner = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let request = mint_request(&e, &farmer, "F1", "S1", 100 * TON);
+    let credit_id = client.mint_locked_carbon_credit(&request, &(NOW + 30 * 86_400), &None);
+    client.verify_credit(&verifier, &credit_id, &VerificationLevel::Basic);
+
+    assert_eq!(
+        client.try_list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None),
+        Err(Ok(CarbonCreditError::CreditLocked))
+    );
+    assert_eq!(
+        client.try_transfer_credit_ownership(&credit_id, &new_owner, &None),
+        Err(Ok(CarbonCreditError::CreditLocked))
+    );
+    // Retiring is not flipping, so the lock does not apply
+    client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+
+    e.ledger().set_timestamp(NOW + 30 * 86_400);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.cancel_order(&order_id, &None);
+    client.transfer_credit_ownership(&credit_id, &new_owner, &None);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().farmer_address, new_owner);
+}
+
+#[test]
+fn test_recent_trades_newest_first_and_bounded() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(50 * TON), &None, &None, &None);
+
+    // Older trades filling the feed up to three short of its cap
+    let stored_trades = || -> Vec<TradeRecord> {
+        e.as_contract(&client.address, || e.storage().persistent().get(&DataKey::RecentTrades).unwrap())
+    };
+    e.as_contract(&client.address, || {
+        let mut trades = Vec::new(&e);
+        for i in 0..MAX_RECENT_TRADES as i128 - 3 {
+            trades.push_back(TradeRecord {
+                order_id: order_id.clone(),
+                credit_id: credit_id.clone(),
+                seller: farmer.clone(),
+                buyer: buyer.clone(),
+                price_per_ton: 1_000_000,
+                amount: -i,
+                timestamp: 0,
+            });
+        }
+        e.storage().persistent().set(&DataKey::RecentTrades, &trades);
+    });
+
+    for tons in 1..=5 {
+        client.buy_carbon_credits(&order_id, &buyer, &(tons * TON), &i128::MAX);
+    }
+
+    let recent = client.get_recent_trades(&5);
+    let amounts: std::vec::Vec<i128> = recent.iter().map(|trade| trade.amount).collect();
+    assert_eq!(amounts, [5 * TON, 4 * TON, 3 * TON, 2 * TON, TON]);
+    assert_eq!(recent.get_unchecked(0).buyer, buyer);
+    assert_eq!(client.get_recent_trades(&u32::MAX).len(), MAX_PAGE_SIZE);
+
+    // The two oldest records were evicted to keep the feed at its cap
+    let stored = stored_trades();
+    assert_eq!(stored.len(), MAX_RECENT_TRADES);
+    assert_eq!(stored.get_unchecked(0).amount, -2);
+}
+
+#[test]
+fn test_restricted_order_sells_only_to_role_holders() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let accredited = Address::generate(&e);
+    let anyone = Address::generate(&e);
+    payment.mint(&accredited, &1_000_000_000);
+    payment.mint(&anyone, &1_000_000_000);
+    let role = symbol_short!("accredit");
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let restricted = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &Some(role.clone()), &None, &None);
+    let open = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.grant_buyer_role(&admin, &accredited, &role);
+
+    assert_eq!(client.get_buyer_restriction(&restricted), Some(role.clone()));
+    assert_eq!(
+        client.try_buy_carbon_credits(&restricted, &anyone, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    client.buy_carbon_credits(&restricted, &accredited, &TON, &i128::MAX);
+    client.buy_carbon_credits(&open, &anyone, &TON, &i128::MAX);
+
+    client.revoke_buyer_role(&admin, &accredited, &role);
+    assert_eq!(
+        client.try_buy_carbon_credits(&restricted, &accredited, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+}
+
+#[test]
+fn test_provenance_records_lifecycle_in_order() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.add_verifier(&verifier);
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    e.ledger().set_timestamp(NOW + 10);
+    client.verify_credit(&verifier, &credit_id, &VerificationLevel::Basic);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(40 * TON), &None, &None, &None);
+    e.ledger().set_timestamp(NOW + 20);
+    client.buy_carbon_credits(&order_id, &buyer, &(15 * TON), &i128::MAX);
+    client.retire_credits(&credit_id, &farmer, &(5 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    let entry = |action: &str, actor: &Address, amount: i128, timestamp: u64| ProvenanceEntry {
+        action: Symbol::new(&e, action),
+        actor: actor.clone(),
+        amount,
+        timestamp,
+    };
+    assert_eq!(
+        client.get_credit_provenance(&credit_id, &0, &10),
+        vec![
+            &e,
+            entry("mint", &farmer, 100 * TON, NOW),
+            entry("verify", &verifier, 100 * TON, NOW + 10),
+            entry("list", &farmer, 40 * TON, NOW + 10),
+            entry("trade", &buyer, 15 * TON, NOW + 20),
+            entry("retire", &farmer, 5 * TON, NOW + 20),
+        ]
+    );
+    assert_eq!(client.get_credit_provenance(&credit_id, &3, &1).get_unchecked(0).action, Symbol::new(&e, "trade"));
+    assert_eq!(client.get_credit_provenance(&credit_id, &5, &10).len(), 0);
+
+    // The buyer's record starts with the trade that created it
+    let bought_id = client.list_credits_by_farmer(&buyer, &0, &1).get_unchecked(0).id;
+    assert_eq!(
+        client.get_credit_provenance(&bought_id, &0, &10),
+        vec![&e, entry("trade", &buyer, 15 * TON, NOW + 20)]
+    );
+}
+
+#[test]
+fn test_disabled_level_rejected_for_mints_and_verifications() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let pending_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    client.set_level_accepted(&VerificationLevel::Basic, &false);
+    assert!(!client.is_level_accepted(&VerificationLevel::Basic));
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 10 * TON)),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    assert_eq!(
+        client.try_verify_credit(&verifier, &pending_id, &VerificationLevel::Basic),
+        Err(Ok(CarbonCreditError::VerificationFailed))
+    );
+    let mut request = mint_request(&e, &farmer, "F1", "S2", 10 * TON);
+    request.verification_level = VerificationLevel::Standard;
+    try_mint(&client, &request).unwrap();
+    client.verify_credit(&verifier, &pending_id, &VerificationLevel::Standard);
+
+    client.set_level_accepted(&VerificationLevel::Basic, &true);
+    assert!(client.is_level_accepted(&VerificationLevel::Basic));
+    mint_pending(&e, &client, &farmer, "F1", "S3", 10 * TON);
+}
+
+#[test]
+fn test_token_transfers_respect_blocklist() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let recipient = Address::generate(&e);
+    let spender = Address::generate(&e);
+    mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+
+    client.transfer(&farmer, &recipient, &TON);
+    assert_eq!(last_signer(&e), farmer);
+    assert_eq!(client.balance(&recipient), TON);
+    client.approve(&farmer, &spender, &(10 * TON), &1_000);
+    client.transfer_from(&spender, &farmer, &recipient, &TON);
+    assert_eq!(client.balance(&recipient), 2 * TON);
+
+    client.block_address(&farmer);
+    assert_eq!(client.try_transfer(&farmer, &recipient, &TON), Err(Ok(CarbonCreditError::NotAuthorized.into())));
+    assert_eq!(
+        client.try_transfer_from(&spender, &farmer, &recipient, &TON),
+        Err(Ok(CarbonCreditError::NotAuthorized.into()))
+    );
+    client.unblock_address(&farmer);
+    client.block_address(&recipient);
+    assert_eq!(client.try_transfer(&farmer, &recipient, &TON), Err(Ok(CarbonCreditError::NotAuthorized.into())));
+    assert_eq!(client.balance(&farmer), 98 * TON);
+}
+
+#[test]
+fn test_token_transfers_recorded_in_sender_credit_provenance() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let recipient = Address::generate(&e);
+    let spender = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 40 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 20 * TON);
+    let transfer = |amount: i128, timestamp: u64| ProvenanceEntry {
+        action: symbol_short!("tok_xfer"),
+        actor: recipient.clone(),
+        amount,
+        timestamp,
+    };
+
+    // The transfer is charged to the credits in index order, spilling into the next
+    e.ledger().set_timestamp(NOW + 10);
+    client.transfer(&farmer, &recipient, &(50 * TON));
+    assert_eq!(client.get_credit_provenance(&first_id, &1, &10), vec![&e, transfer(40 * TON, NOW + 10)]);
+    assert_eq!(client.get_credit_provenance(&second_id, &1, &10), vec![&e, transfer(10 * TON, NOW + 10)]);
+
+    e.ledger().set_timestamp(NOW + 20);
+    client.approve(&farmer, &spender, &(5 * TON), &1_000);
+    client.transfer_from(&spender, &farmer, &recipient, &(5 * TON));
+    assert_eq!(
+        client.get_credit_provenance(&first_id, &1, &10),
+        vec![&e, transfer(40 * TON, NOW + 10), transfer(5 * TON, NOW + 20)]
+    );
+    assert_eq!(client.get_credit_provenance(&second_id, &1, &10).len(), 1);
+}
+
+#[test]
+fn test_credit_lookup_by_report_hash() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    mint_pending(&e, &client, &farmer, "F1", "S2", 20 * TON);
+
+    assert_eq!(
+        client.get_credit_by_report_hash(&report_hash(&e, "F1S1")),
+        client.get_carbon_credit(&first_id)
+    );
+    assert_eq!(client.get_credit_by_report_hash(&report_hash(&e, "F1S2")).unwrap().carbon_amount, 20 * TON);
+    assert_eq!(client.get_credit_by_report_hash(&report_hash(&e, "F1S3")), None);
+}
+
+#[test]
+fn test_repriced_order_sells_at_new_price() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+
+    assert_eq!(client.try_update_order_price(&order_id, &0, &None), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    client.update_order_price(&order_id, &3_000_000, &None);
+    assert_eq!(last_signer(&e), farmer);
+    client.buy_carbon_credits(&order_id, &buyer, &(2 * TON), &i128::MAX);
+
+    assert_eq!(client.get_contract_stats().total_market_volume, 6_000_000);
+    let history = client.get_credit_provenance(&credit_id, &0, &10);
+    let reprice = history.get_unchecked(history.len() - 2);
+    assert_eq!((reprice.action, reprice.amount), (symbol_short!("reprice"), 10 * TON));
+
+    // Once sold out the order can no longer be repriced
+    client.buy_carbon_credits(&order_id, &buyer, &(8 * TON), &i128::MAX);
+    assert_eq!(
+        client.try_update_order_price(&order_id, &1_000_000, &None),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_price_circuit_breaker() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_price_deviation(&2_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(50 * TON), &None, &None, &None);
+
+    // Without enough history even an extreme first trade goes through
+    let fresh_id = mint_verified(&e, &client, &farmer, "F1", "S2", 100 * TON);
+    let fresh_order = client.list_for_sale(&fresh_id, &50_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&fresh_order, &buyer, &TON, &i128::MAX);
+
+    for _ in 0..3 {
+        client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    }
+    client.update_order_price(&order_id, &1_150_000, &None);
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    client.update_order_price(&order_id, &2_000_000, &None);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::PriceOutOfBounds))
+    );
+    client.update_order_price(&order_id, &500_000, &None);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::PriceOutOfBounds))
+    );
+}
+
+#[test]
+fn test_reserved_and_available_after_two_partial_listings() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    assert_eq!(client.get_reserved_amount(&credit_id), 0);
+    assert_eq!(client.get_available_amount(&credit_id), 100 * TON);
+
+    let first = client.list_for_sale(&credit_id, &1_000_000, &(25 * TON), &None, &None, &None);
+    client.list_for_sale(&credit_id, &2_000_000, &(15 * TON), &None, &None, &None);
+    assert_eq!(client.get_reserved_amount(&credit_id), 40 * TON);
+    assert_eq!(client.get_available_amount(&credit_id), 60 * TON);
+
+    client.cancel_order(&first, &None);
+    assert_eq!(client.get_reserved_amount(&credit_id), 15 * TON);
+    assert_eq!(client.get_available_amount(&credit_id), 85 * TON);
+}
+
+#[test]
+fn test_one_credit_per_farm_season_unless_amended() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let farm = String::from_str(&e, "F1");
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    mint_pending(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    mint_pending(&e, &client, &farmer, "F2", "S1", 10 * TON);
+
+    let mut amended = mint_request(&e, &farmer, "F1", "S1", 12 * TON);
+    amended.report_hash = report_hash(&e, "F1S1v2");
+    assert_eq!(try_mint(&client, &amended), Err(Ok(CarbonCreditError::CreditAlreadyExists)));
+    assert_eq!(
+        client.try_allow_season_amendment(&farm, &String::from_str(&e, "S9")),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+
+    // An amendment allowance covers exactly one more mint
+    client.allow_season_amendment(&farm, &String::from_str(&e, "S1"));
+    let amended_id = try_mint(&client, &amended).unwrap();
+    assert_ne!(amended_id, first_id);
+    assert_eq!(client.get_credit_for_season(&farm, &String::from_str(&e, "S1")), Some(amended_id));
+    amended.report_hash = report_hash(&e, "F1S1v3");
+    assert_eq!(try_mint(&client, &amended), Err(Ok(CarbonCreditError::CreditAlreadyExists)));
+}
+
+#[test]
+fn test_set_and_read_credit_metadata_value() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let outsider = Address::generate(&e);
+    let credit_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let key = String::from_str(&e, "irrigation");
+    let value = String::from_str(&e, "AWD");
+    assert_eq!(client.get_credit_metadata_value(&credit_id, &key), None);
+
+    client.set_credit_metadata_value(&farmer, &credit_id, &key, &value);
+    assert_eq!(client.get_credit_metadata_value(&credit_id, &key), Some(value));
+    assert_eq!(
+        client.try_set_credit_metadata_value(&outsider, &credit_id, &key, &String::from_str(&e, "flooded")),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+
+    // New keys stop at the cap, existing ones can still be updated
+    for i in 1..MAX_METADATA_KEYS {
+        let key = String::from_str(&e, &std::format!("key{i}"));
+        client.set_credit_metadata_value(&farmer, &credit_id, &key, &String::from_str(&e, "x"));
+    }
+    assert_eq!(
+        client.try_set_credit_metadata_value(&farmer, &credit_id, &String::from_str(&e, "extra"), &String::from_str(&e, "x")),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    client.set_credit_metadata_value(&farmer, &credit_id, &key, &String::from_str(&e, "flooded"));
+    assert_eq!(client.get_credit_metadata_value(&credit_id, &key), Some(String::from_str(&e, "flooded")));
+}
+
+#[test]
+fn test_retirements_filtered_by_reason() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let note = Some(String::from_str(&e, "On behalf of Mandla school"));
+
+    let voluntary = client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+    let donation = client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::Donation, &note);
+    let second_voluntary = client.retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(
+        client.get_retirements_by_reason(&RetirementReason::VoluntaryOffset, &0, &10),
+        vec![&e, voluntary, second_voluntary.clone()]
+    );
+    assert_eq!(client.get_retirements_by_reason(&RetirementReason::VoluntaryOffset, &1, &10), vec![&e, second_voluntary]);
+    assert_eq!(client.get_retirements_by_reason(&RetirementReason::Donation, &0, &10), vec![&e, donation.clone()]);
+    assert_eq!(client.get_retirements_by_reason(&RetirementReason::ComplianceOffset, &0, &10).len(), 0);
+    let certificate = client.get_retirement_certificate(&donation).unwrap();
+    assert_eq!((certificate.reason, certificate.note), (RetirementReason::Donation, note));
+}
+
+#[test]
+fn test_vintage_stats_track_minted_and_retired() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    mint_verified(&e, &client, &farmer, "F1", "S1", 50 * TON);
+    let mut request = mint_request(&e, &farmer, "F1", "S0", 30 * TON);
+    request.vintage = 2022;
+    let older_id = try_mint(&client, &request).unwrap();
+    let mut request = mint_request(&e, &farmer, "F2", "S0", 20 * TON);
+    request.vintage = 2022;
+    try_mint(&client, &request).unwrap();
+
+    client.retire_credits(&older_id, &farmer, &(12 * TON), &RetirementReason::VoluntaryOffset, &None);
+
+    assert_eq!(client.get_vintage_stats(&2022), (50 * TON, 12 * TON));
+    assert_eq!(client.get_vintage_stats(&2023), (50 * TON, 0));
+    assert_eq!(client.get_vintage_stats(&2021), (0, 0));
+}
+
+#[test]
+fn test_list_all_credits_pages_through_every_record() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let mut minted = std::vec::Vec::new();
+    for farm in ["F1", "F2", "F3", "F4", "F5"] {
+        let farmer = Address::generate(&e);
+        minted.push(mint_pending(&e, &client, &farmer, farm, "S1", 10 * TON));
+    }
+
+    assert_eq!(client.get_credit_count(), 5);
+    let first_page = client.list_all_credits(&0, &3);
+    let second_page = client.list_all_credits(&3, &3);
+    assert_eq!((first_page.len(), second_page.len()), (3, 2));
+    let ids: std::vec::Vec<String> = first_page.iter().chain(second_page.iter()).map(|credit| credit.id).collect();
+    assert_eq!(ids, minted);
+    assert_eq!(client.list_all_credits(&5, &3).len(), 0);
+}
+
+#[test]
+fn test_removing_from_an_index_moves_its_last_id_into_place() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cooperative = Address::generate(&e);
+    let first = mint_verified(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second = mint_verified(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    let third = mint_verified(&e, &client, &farmer, "F1", "S3", 10 * TON);
+    let ids = |owner: &Address| -> std::vec::Vec<String> {
+        client.list_credits_by_farmer(owner, &0, &10).iter().map(|credit| credit.id).collect()
+    };
+
+    client.transfer_credit_ownership(&first, &cooperative, &None);
+    assert_eq!(ids(&farmer), std::vec![third.clone(), second.clone()]);
+    client.transfer_credit_ownership(&second, &cooperative, &None);
+    assert_eq!(ids(&farmer), std::vec![third.clone()]);
+    assert_eq!(ids(&cooperative), std::vec![first.clone(), second.clone()]);
+
+    // Ids that left an index can join it again
+    client.transfer_credit_ownership(&first, &farmer, &None);
+    assert_eq!(ids(&farmer), std::vec![third, first]);
+    assert_eq!(ids(&cooperative), std::vec![second]);
+    assert_eq!(client.get_credit_count(), 3);
+}
+
+#[test]
+fn test_mint_blocked_while_supply_and_totals_disagree() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    edit_state(&e, &client, |state| state.total_credits_minted += TON);
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S2", 10 * TON)),
+        Err(Ok(CarbonCreditError::StateInconsistent))
+    );
+    assert_eq!(
+        client.try_batch_mint_carbon_credits(&vec![&e, mint_request(&e, &farmer, "F1", "S2", 10 * TON)], &vec![&e]),
+        Err(Ok(CarbonCreditError::StateInconsistent))
+    );
+
+    edit_state(&e, &client, |state| state.total_credits_minted -= TON);
+    mint_pending(&e, &client, &farmer, "F1", "S2", 10 * TON);
+}
+
+#[test]
+fn test_order_history_covers_every_status() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let filled = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    let cancelled = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let active = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&filled, &buyer, &(4 * TON), &i128::MAX);
+    e.ledger().set_timestamp(NOW + 60);
+    client.buy_carbon_credits(&filled, &buyer, &(6 * TON), &i128::MAX);
+    client.cancel_order(&cancelled, &None);
+
+    let orders = client.list_orders(&0, &10);
+    let statuses: std::vec::Vec<(String, String)> = orders.iter().map(|order| (order.id, order.status)).collect();
+    assert_eq!(
+        statuses,
+        [
+            (filled.clone(), String::from_str(&e, "Filled")),
+            (cancelled, String::from_str(&e, "Cancelled")),
+            (active, String::from_str(&e, "Active")),
+        ]
+    );
+    assert_eq!(
+        client.get_order_fill(&filled),
+        Some(OrderFill { filled_amount: 10 * TON, last_price_per_ton: 2_000_000, last_filled_at: NOW + 60 })
+    );
+    assert_eq!(client.list_orders(&2, &10).len(), 1);
+}
+
+#[test]
+fn test_withdraw_accumulated_fees_in_parts() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let treasury = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_fee_settings(&500, &admin);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(20 * TON), &None, &None, &None);
+
+    client.buy_carbon_credits(&order_id, &buyer, &(10 * TON), &i128::MAX);
+    client.buy_carbon_credits(&order_id, &buyer, &(10 * TON), &i128::MAX);
+    assert_eq!(client.get_contract_stats().accumulated_fees, 2_000_000);
+    assert_eq!(payment_token.balance(&client.address), 2_000_000);
+
+    client.withdraw_fees(&treasury, &1_500_000);
+    assert_eq!(last_signer(&e), admin);
+    assert_eq!(payment_token.balance(&treasury), 1_500_000);
+    assert_eq!(
+        client.try_withdraw_fees(&treasury, &500_001),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+    client.withdraw_fees(&treasury, &500_000);
+    assert_eq!(payment_token.balance(&treasury), 2_000_000);
+    assert_eq!(client.get_contract_stats().accumulated_fees, 0);
+    assert_eq!(client.try_withdraw_fees(&treasury, &1), Err(Ok(CarbonCreditError::InsufficientBalance)));
+}
+
+#[test]
+fn test_top_farmers_ranked_by_lifetime_minted() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let small = Address::generate(&e);
+    let large = Address::generate(&e);
+    let medium = Address::generate(&e);
+    let tiny = Address::generate(&e);
+    mint_pending(&e, &client, &small, "F1", "S1", 20 * TON);
+    mint_pending(&e, &client, &large, "F2", "S1", 60 * TON);
+    mint_pending(&e, &client, &medium, "F3", "S1", 30 * TON);
+    mint_pending(&e, &client, &tiny, "F4", "S1", 5 * TON);
+    // A second season lifts the small farmer past the medium one
+    mint_pending(&e, &client, &small, "F1", "S2", 15 * TON);
+
+    assert_eq!(
+        client.get_top_farmers(&3),
+        vec![&e, (large, 60 * TON), (small, 35 * TON), (medium, 30 * TON)]
+    );
+    assert_eq!(client.get_top_farmers(&10).len(), 4);
+}
+
+#[test]
+fn test_listing_needs_the_owners_signature() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let outsider = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let price = 1_000_000_i128;
+    let amount = 10 * TON;
+    let invoke = MockAuthInvoke {
+        contract: &client.address,
+        fn_name: "list_for_sale",
+        args: (credit_id.clone(), price, amount, None::<Symbol>, None::<Address>, None::<Address>).into_val(&e),
+        sub_invokes: &[],
+    };
+
+    e.mock_auths(&[MockAuth { address: &outsider, invoke: &invoke }]);
+    assert!(matches!(
+        client.try_list_for_sale(&credit_id, &price, &amount, &None, &None, &None),
+        Err(Err(_))
+    ));
+
+    e.mock_auths(&[MockAuth { address: &farmer, invoke: &invoke }]);
+    let order_id = client.list_for_sale(&credit_id, &price, &amount, &None, &None, &None);
+    assert_eq!(client.get_market_order(&order_id).unwrap().seller_address, farmer);
+}
+
+#[test]
+fn test_reverified_credit_tradable_again_after_expiry() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    client.update_validity_years(&2);
+    e.ledger().set_timestamp(NOW + 365 * 86_400);
+    assert!(client.is_credit_expired(&credit_id));
+    let new_hash = report_hash(&e, "F1S1v2");
+
+    assert_eq!(
+        client.try_reverify_credit(&farmer, &credit_id, &new_hash),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_reverify_credit(&verifier, &credit_id, &report_hash(&e, "F1S1")),
+        Err(Ok(CarbonCreditError::CreditAlreadyExists))
+    );
+    client.reverify_credit(&verifier, &credit_id, &new_hash);
+
+    assert!(!client.is_credit_expired(&credit_id));
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().report_hash, new_hash);
+    assert_eq!(client.get_credit_by_report_hash(&new_hash).unwrap().id, credit_id);
+    client.list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None);
+    let history = client.get_credit_provenance(&credit_id, &0, &10);
+    assert_eq!(history.get_unchecked(2).action, symbol_short!("reverify"));
+    assert_eq!(history.get_unchecked(2).actor, verifier);
+
+    // Valid through 2028, two years after the 2026 re-verification
+    e.ledger().set_timestamp(NOW + 4 * 365 * 86_400);
+    assert!(!client.is_credit_expired(&credit_id));
+    e.ledger().set_timestamp(NOW + 5 * 365 * 86_400);
+    assert!(client.is_credit_expired(&credit_id));
+}
+
+#[test]
+fn test_average_price_weighted_by_volume_per_vintage() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.add_verifier(&verifier);
+    let recent_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let mut request = mint_request(&e, &farmer, "F1", "S0", 100 * TON);
+    request.vintage = 2022;
+    let older_id = try_mint(&client, &request).unwrap();
+    client.verify_credit(&verifier, &older_id, &VerificationLevel::Basic);
+    assert_eq!(client.get_average_price(&2023), None);
+
+    let cheap = client.list_for_sale(&recent_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    let dear = client.list_for_sale(&recent_id, &2_000_000, &(30 * TON), &None, &None, &None);
+    let older = client.list_for_sale(&older_id, &4_000_000, &(5 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&cheap, &buyer, &(10 * TON), &i128::MAX);
+    client.buy_carbon_credits(&dear, &buyer, &(30 * TON), &i128::MAX);
+    client.buy_carbon_credits(&older, &buyer, &(5 * TON), &i128::MAX);
+
+    // (10 * 1 + 30 * 2) / 40 tons
+    assert_eq!(client.get_average_price(&2023), Some(1_750_000));
+    assert_eq!(client.get_average_price(&2022), Some(4_000_000));
+    assert_eq!(client.get_average_price(&2021), None);
+}
+
+#[test]
+fn test_bulk_verify_mixed_batch_strict_and_lenient() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    let verified_id = mint_verified(&e, &client, &farmer, "F1", "S3", 10 * TON);
+    let unknown_id = String::from_str(&e, "F9_S9_2023_0_99");
+    let batch = vec![&e, first_id.clone(), verified_id, unknown_id, second_id.clone()];
+
+    // Strict mode rejects the whole batch over one credit that is not pending
+    assert_eq!(
+        client.try_bulk_verify_credits(&verifier, &batch, &VerificationLevel::Standard, &true),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+    assert_eq!(client.get_carbon_credit(&first_id).unwrap().status, CreditStatus::Pending);
+
+    let verified = client.bulk_verify_credits(&verifier, &batch, &VerificationLevel::Standard, &false);
+    assert_eq!(verified, vec![&e, first_id.clone(), second_id.clone()]);
+    for credit_id in [first_id, second_id] {
+        let credit = client.get_carbon_credit(&credit_id).unwrap();
+        assert_eq!((credit.status, credit.verification_level), (CreditStatus::Verified, VerificationLevel::Standard));
+    }
+    assert_eq!(
+        client.try_bulk_verify_credits(&farmer, &batch, &VerificationLevel::Standard, &false),
+        Err(Ok(CarbonCreditError::NotAuthorized))
+    );
+    assert_eq!(
+        client.try_bulk_verify_credits(&verifier, &Vec::new(&e), &VerificationLevel::Standard, &false),
+        Err(Ok(CarbonCreditError::InvalidCreditData))
+    );
+}
+
+#[test]
+fn test_pending_queue_drops_verified_and_cancelled_credits() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    let third_id = mint_pending(&e, &client, &farmer, "F1", "S3", 10 * TON);
+
+    client.verify_credit(&verifier, &second_id, &VerificationLevel::Basic);
+    let pending: std::vec::Vec<String> = client.list_pending_credits(&0, &10).iter().map(|c| c.id).collect();
+    assert_eq!(pending, [first_id.clone(), third_id.clone()]);
+
+    // A pending credit cancelled over an upheld flag leaves the queue too
+    client.flag_credit(&auditor, &third_id, &String::from_str(&e, "duplicate plot"));
+    client.resolve_flag(&verifier, &third_id, &true);
+    assert_eq!(client.get_carbon_credit(&third_id).unwrap().status, CreditStatus::Cancelled);
+    let pending = client.list_pending_credits(&0, &10);
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending.get_unchecked(0).id, first_id);
+}
+
+#[test]
+fn test_buy_rejects_total_above_buyers_max() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &2_000_000, &(20 * TON), &None, &None, &None);
+
+    // The seller reprices after the buyer quoted 20 for 10 tons
+    client.update_order_price(&order_id, &3_000_000, &None);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &(10 * TON), &20_000_000),
+        Err(Ok(CarbonCreditError::SlippageExceeded))
+    );
+    assert_eq!(payment_token.balance(&buyer), 1_000_000_000);
+    client.buy_carbon_credits(&order_id, &buyer, &(10 * TON), &30_000_000);
+
+    // An auction bought early costs more than its later, lower price
+    let auction_id = client.list_for_auction(&credit_id, &10_000_000, &2_000_000, &1_000, &(10 * TON), &None);
+    assert_eq!(
+        client.try_buy_carbon_credits(&auction_id, &buyer, &TON, &6_000_000),
+        Err(Ok(CarbonCreditError::SlippageExceeded))
+    );
+    e.ledger().set_timestamp(NOW + 500);
+    client.buy_carbon_credits(&auction_id, &buyer, &TON, &6_000_000);
+    assert_eq!(payment_token.balance(&buyer), 1_000_000_000 - 30_000_000 - 6_000_000);
+}
+
+#[test]
+fn test_market_governance_calls_publish_events() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, _) = setup(&e);
+
+    client.pause_market();
+    let data = event_data(&e, &client, (symbol_short!("market"), symbol_short!("paused"))).unwrap();
+    assert_eq!(Address::try_from_val(&e, &data).unwrap(), admin);
+
+    client.resume_market();
+    let data = event_data(&e, &client, (symbol_short!("market"), symbol_short!("resumed"))).unwrap();
+    assert_eq!(Address::try_from_val(&e, &data).unwrap(), admin);
+
+    client.update_market_settings(&false, &VerificationLevel::Premium);
+    let data = event_data(&e, &client, (symbol_short!("market"), symbol_short!("settings"))).unwrap();
+    assert_eq!(
+        <(Address, bool, VerificationLevel)>::try_from_val(&e, &data).unwrap(),
+        (admin, false, VerificationLevel::Premium)
+    );
+}
+
+#[test]
+fn test_simulated_quote_matches_charged_amounts() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let reseller = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&reseller, &1_000_000_000);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_fee_settings(&250, &admin);
+    client.update_royalty_settings(&300);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(20 * TON), &None, &None, &None);
+    client.buy_carbon_credits(&order_id, &reseller, &(20 * TON), &i128::MAX);
+    let bought_id = client.list_credits_by_farmer(&reseller, &0, &1).get_unchecked(0).id;
+
+    // A resale auction a quarter of the way down its decay curve, at 8 per ton
+    let auction_id = client.list_for_auction(&bought_id, &10_000_000, &2_000_000, &1_000, &(20 * TON), &None);
+    e.ledger().set_timestamp(NOW + 250);
+    let amount = 3 * TON + 333_333;
+    let quote = client.simulate_buy(&auction_id, &amount);
+    assert_eq!(quote.base_price, 26_666_664);
+    assert_eq!(quote.total, quote.base_price);
+
+    let farmer_before = payment_token.balance(&farmer);
+    let reseller_before = payment_token.balance(&reseller);
+    let fees_before = client.get_contract_stats().accumulated_fees;
+    client.buy_carbon_credits(&auction_id, &buyer, &amount, &quote.total);
+    assert_eq!(payment_token.balance(&buyer), 1_000_000_000 - quote.total);
+    assert_eq!(client.get_contract_stats().accumulated_fees - fees_before, quote.fee);
+    assert_eq!(payment_token.balance(&farmer) - farmer_before, quote.royalty);
+    assert_eq!(payment_token.balance(&reseller) - reseller_before, quote.total - quote.fee - quote.royalty);
+    assert_eq!(
+        client.try_simulate_buy(&auction_id, &(17 * TON)),
+        Err(Ok(CarbonCreditError::InsufficientBalance))
+    );
+}
+
+#[test]
+fn test_retiree_lists_certificates_from_two_credits() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let first_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let second_id = mint_verified(&e, &client, &farmer, "F2", "S1", 100 * TON);
+    for credit_id in [&first_id, &second_id] {
+        let order_id = client.list_for_sale(credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+        client.buy_carbon_credits(&order_id, &buyer, &(10 * TON), &i128::MAX);
+    }
+    let held = client.list_credits_by_farmer(&buyer, &0, &10);
+
+    let first = client.retire_credits(&held.get_unchecked(0).id, &buyer, &(4 * TON), &RetirementReason::VoluntaryOffset, &None);
+    let second = client.retire_credits(&held.get_unchecked(1).id, &buyer, &(6 * TON), &RetirementReason::VoluntaryOffset, &None);
+    client.retire_credits(&first_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None);
+
+    let certificates = client.list_certificates_by_retiree(&buyer, &0, &10);
+    let ids: std::vec::Vec<String> = certificates.iter().map(|certificate| certificate.id).collect();
+    assert_eq!(ids, [first, second.clone()]);
+    assert_eq!(certificates.get_unchecked(1).amount, 6 * TON);
+    assert_eq!(client.list_certificates_by_retiree(&buyer, &1, &10).get_unchecked(0).id, second);
+    assert_eq!(client.list_certificates_by_retiree(&farmer, &0, &10).len(), 1);
+}
+
+#[test]
+fn test_floor_and_half_up_rounding_of_fees() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    client.update_fee_settings(&250, &admin);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    // 2.5% of 7_779 is 194.475 and of 7_781 is 194.525
+    let low = client.list_for_sale(&credit_id, &7_779, &TON, &None, &None, &None);
+    let high = client.list_for_sale(&credit_id, &7_781, &TON, &None, &None, &None);
+
+    assert_eq!(client.get_config().rounding_mode, RoundingMode::Floor);
+    assert_eq!(client.simulate_buy(&low, &TON).fee, 194);
+    assert_eq!(client.simulate_buy(&high, &TON).fee, 194);
+
+    client.update_rounding_mode(&RoundingMode::HalfUp);
+    assert_eq!(client.simulate_buy(&low, &TON).fee, 194);
+    assert_eq!(client.simulate_buy(&high, &TON).fee, 195);
+
+    // The seller absorbs the rounded-up half unit
+    client.buy_carbon_credits(&high, &buyer, &TON, &i128::MAX);
+    assert_eq!(payment_token.balance(&farmer), 7_781 - 195);
+    assert_eq!(client.get_contract_stats().accumulated_fees, 195);
+}
+
+#[test]
+fn test_ownership_history_follows_transfers() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let cooperative = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+
+    e.ledger().set_timestamp(NOW + 100);
+    client.transfer_credit_ownership(&credit_id, &cooperative, &None);
+    e.ledger().set_timestamp(NOW + 200);
+    client.transfer_credit_ownership(&credit_id, &buyer, &None);
+
+    assert_eq!(
+        client.get_ownership_history(&credit_id),
+        vec![&e, (farmer, NOW), (cooperative, NOW + 100), (buyer, NOW + 200)]
+    );
+    assert_eq!(client.get_ownership_history(&String::from_str(&e, "unknown")).len(), 0);
+}
+
+#[test]
+fn test_token_freeze_blocks_every_transfer_path() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    let spender = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(10 * TON), &None, &None, &None);
+    client.approve(&farmer, &spender, &(10 * TON), &1_000);
+
+    client.freeze_tokens();
+    let frozen = Err(Ok(soroban_sdk::Error::from(CarbonCreditError::TokensFrozen)));
+    assert_eq!(client.try_transfer(&farmer, &buyer, &TON), frozen);
+    assert_eq!(client.try_transfer_from(&spender, &farmer, &buyer, &TON), frozen);
+    assert_eq!(
+        client.try_buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX),
+        Err(Ok(CarbonCreditError::TokensFrozen))
+    );
+    assert_eq!(
+        client.try_retire_credits(&credit_id, &farmer, &TON, &RetirementReason::VoluntaryOffset, &None),
+        Err(Ok(CarbonCreditError::TokensFrozen))
+    );
+    assert_eq!(
+        client.try_transfer_credit_ownership(&mint_verified(&e, &client, &farmer, "F1", "S2", 10 * TON), &buyer, &None),
+        Err(Ok(CarbonCreditError::TokensFrozen))
+    );
+    assert_eq!(client.balance(&farmer), 100 * TON);
+
+    client.unfreeze_tokens();
+    client.transfer(&farmer, &buyer, &TON);
+    client.buy_carbon_credits(&order_id, &buyer, &TON, &i128::MAX);
+    assert_eq!(client.balance(&buyer), 2 * TON);
+}
+
+#[test]
+fn test_seller_active_listings_and_value() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let farmer = Address::generate(&e);
+    let other = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &1_000_000_000);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let other_id = mint_verified(&e, &client, &other, "F2", "S1", 100 * TON);
+    let first = client.list_for_sale(&credit_id, &2_000_000, &(10 * TON), &None, &None, &None);
+    let second = client.list_for_sale(&credit_id, &3_000_000, &(5 * TON), &None, &None, &None);
+    client.list_for_sale(&other_id, &9_000_000, &(10 * TON), &None, &None, &None);
+
+    let listings: std::vec::Vec<String> = client.get_seller_active_listings(&farmer, &0, &10).iter().map(|o| o.id).collect();
+    assert_eq!(listings, [first.clone(), second.clone()]);
+    assert_eq!(client.get_seller_active_value(&farmer), 20_000_000 + 15_000_000);
+
+    // Only the unsold amount of active orders counts
+    client.buy_carbon_credits(&first, &buyer, &(4 * TON), &i128::MAX);
+    assert_eq!(client.get_seller_active_value(&farmer), 12_000_000 + 15_000_000);
+    client.buy_carbon_credits(&first, &buyer, &(6 * TON), &i128::MAX);
+    assert_eq!(client.get_seller_active_listings(&farmer, &0, &10).get_unchecked(0).id, second);
+    assert_eq!(client.get_seller_active_value(&farmer), 15_000_000);
+}
+
+#[test]
+fn test_same_ledger_mints_get_distinct_ids() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+
+    // An amended mint for the same farm, season and vintage in the same second
+    client.allow_season_amendment(&String::from_str(&e, "F1"), &String::from_str(&e, "S1"));
+    let mut amended = mint_request(&e, &farmer, "F1", "S1", 12 * TON);
+    amended.report_hash = report_hash(&e, "F1S1v2");
+    let second_id = try_mint(&client, &amended).unwrap();
+
+    assert_ne!(first_id, second_id);
+    assert_eq!(first_id, String::from_str(&e, "F1_S1_2023_1735689600_1"));
+    assert_eq!(second_id, String::from_str(&e, "F1_S1_2023_1735689600_2"));
+    assert_eq!(client.get_carbon_credit(&first_id).unwrap().carbon_amount, 10 * TON);
+    assert_eq!(client.get_carbon_credit(&second_id).unwrap().carbon_amount, 12 * TON);
+}
+
+#[test]
+fn test_failed_payment_reverts_the_whole_trade() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, payment) = setup(&e);
+    let payment_token = token::Client::new(&e, &payment.address);
+    let farmer = Address::generate(&e);
+    let buyer = Address::generate(&e);
+    payment.mint(&buyer, &9_999_999);
+    let credit_id = mint_verified(&e, &client, &farmer, "F1", "S1", 100 * TON);
+    let order_id = client.list_for_sale(&credit_id, &1_000_000, &(20 * TON), &None, &None, &None);
+
+    assert!(client.try_buy_carbon_credits(&order_id, &buyer, &(10 * TON), &i128::MAX).is_err());
+
+    assert_eq!(client.balance(&buyer), 0);
+    assert_eq!(client.balance(&client.address), 20 * TON);
+    assert_eq!(payment_token.balance(&buyer), 9_999_999);
+    assert_eq!(payment_token.balance(&farmer), 0);
+    assert_eq!(client.get_market_order(&order_id).unwrap().amount, 20 * TON);
+    assert_eq!(client.get_carbon_credit(&credit_id).unwrap().carbon_amount, 100 * TON);
+    assert_eq!(client.list_credits_by_farmer(&buyer, &0, &10).len(), 0);
+    assert_eq!(client.get_order_fill(&order_id), None);
+
+    client.buy_carbon_credits(&order_id, &buyer, &(9 * TON), &i128::MAX);
+    assert_eq!(client.balance(&buyer), 9 * TON);
+}
+
+#[test]
+fn test_level_distribution_tracks_live_verified_tons() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let verifier = Address::generate(&e);
+    let auditor = Address::generate(&e);
+    client.add_verifier(&verifier);
+    let basic_id = mint_verified(&e, &client, &farmer, "F1", "S1", 30 * TON);
+    let gold_id = mint_pending(&e, &client, &farmer, "F1", "S2", 50 * TON);
+    client.verify_credit(&verifier, &gold_id, &VerificationLevel::Gold);
+    mint_pending(&e, &client, &farmer, "F1", "S3", 10 * TON);
+
+    let distribution = client.get_level_distribution();
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Basic), 30 * TON);
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Standard), 0);
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Premium), 0);
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Gold), 50 * TON);
+
+    client.retire_credits(&gold_id, &farmer, &(5 * TON), &RetirementReason::VoluntaryOffset, &None);
+    client.flag_credit(&auditor, &basic_id, &String::from_str(&e, "double counted"));
+    client.resolve_flag(&verifier, &basic_id, &true);
+
+    let distribution = client.get_level_distribution();
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Basic), 0);
+    assert_eq!(distribution.get_unchecked(VerificationLevel::Gold), 45 * TON);
+}
+
+#[test]
+fn test_config_reflects_every_setter() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, admin, payment) = setup(&e);
+    let treasury = Address::generate(&e);
+    let oracle = BytesN::from_array(&e, &[9; 32]);
+
+    client.update_market_settings(&false, &VerificationLevel::Standard);
+    client.pause_contract();
+    client.freeze_tokens();
+    client.update_buyer_kyc(&true);
+    client.update_fee_settings(&150, &treasury);
+    client.update_royalty_settings(&200);
+    client.update_rounding_mode(&RoundingMode::HalfUp);
+    client.update_list_fee(&5_000);
+    client.update_mint_rate_limit(&3, &86_400);
+    client.update_price_deviation(&2_500);
+    client.update_min_vintage(&2015);
+    client.update_validity_years(&7);
+    client.update_mint_bounds(&(2 * TON), &(500 * TON));
+    client.update_amount_unit(&1_000);
+    client.update_verifier_pubkey(&Some(oracle.clone()));
+    client.update_ttl_settings(&(10 * DAY_IN_LEDGERS), &(60 * DAY_IN_LEDGERS));
+
+    assert_eq!(
+        client.get_config(),
+        ContractConfig {
+            admin,
+            treasury,
+            payment_token: payment.address,
+            market_open: false,
+            min_verification_level: VerificationLevel::Standard,
+            contract_paused: true,
+            tokens_frozen: true,
+            require_buyer_kyc: true,
+            fee_bps: 150,
+            royalty_bps: 200,
+            rounding_mode: RoundingMode::HalfUp,
+            list_fee: 5_000,
+            mint_rate_limit: 3,
+            mint_rate_window: 86_400,
+            max_price_deviation_bps: 2_500,
+            min_vintage: 2015,
+            validity_years: 7,
+            min_mint_amount: 2 * TON,
+            max_mint_amount: 500 * TON,
+            amount_unit: 1_000,
+            verifier_pubkey: Some(oracle),
+            ttl_threshold: 10 * DAY_IN_LEDGERS,
+            ttl_extend_to: 60 * DAY_IN_LEDGERS,
+        }
+    );
+}
+
+#[test]
+fn test_credits_by_ids_align_with_request() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let first_id = mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    let second_id = mint_pending(&e, &client, &farmer, "F1", "S2", 20 * TON);
+    let unknown_id = String::from_str(&e, "F9_S9_2023_0_99");
+
+    let credits = client.get_credits_by_ids(&vec![&e, second_id.clone(), unknown_id.clone(), first_id.clone(), second_id.clone()]);
+    assert_eq!(
+        credits,
+        vec![
+            &e,
+            client.get_carbon_credit(&second_id),
+            None,
+            client.get_carbon_credit(&first_id),
+            client.get_carbon_credit(&second_id),
+        ]
+    );
+
+    let mut too_many = Vec::new(&e);
+    for _ in 0..=MAX_PAGE_SIZE {
+        too_many.push_back(unknown_id.clone());
+    }
+    assert_eq!(client.try_get_credits_by_ids(&too_many), Err(Ok(CarbonCreditError::InvalidCreditData.into())));
+}
+
+#[test]
+fn test_terminal_credits_reject_split_merge_transfer_and_listing() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let retired_id = mint_verified(&e, &client, &farmer, "F1", "S1", 40 * TON);
+    let live_id = mint_verified(&e, &client, &farmer, "F1", "S2", 40 * TON);
+    let merged_away_id = mint_verified(&e, &client, &farmer, "F1", "S3", 40 * TON);
+    let other_id = mint_verified(&e, &client, &farmer, "F1", "S4", 40 * TON);
+    client.retire_credits(&retired_id, &farmer, &(40 * TON), &RetirementReason::VoluntaryOffset, &None);
+    client.merge_credits(&vec![&e, merged_away_id.clone(), other_id], &None);
+
+    for credit_id in [retired_id, merged_away_id] {
+        assert_eq!(client.try_split_credit(&credit_id, &TON, &None), Err(Ok(CarbonCreditError::InvalidCreditData)));
+        assert_eq!(
+            client.try_merge_credits(&vec![&e, credit_id.clone(), live_id.clone()], &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+        assert_eq!(
+            client.try_transfer_credit_ownership(&credit_id, &Address::generate(&e), &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+        assert_eq!(
+            client.try_list_for_sale(&credit_id, &1_000_000, &TON, &None, &None, &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+        assert_eq!(
+            client.try_distribute_credits(&credit_id, &vec![&e, (Address::generate(&e), TON)], &None),
+            Err(Ok(CarbonCreditError::InvalidCreditData))
+        );
+    }
+    client.split_credit(&live_id, &TON, &None);
+}
+
+#[test]
+fn test_mint_rate_limit_per_farmer_rolling_window() {
+    let e = Env::default();
+    e.mock_all_auths();
+    let (client, _, _) = setup(&e);
+    let farmer = Address::generate(&e);
+    let neighbour = Address::generate(&e);
+    assert_eq!(client.try_update_mint_rate_limit(&2, &0), Err(Ok(CarbonCreditError::InvalidCreditData)));
+    client.update_mint_rate_limit(&2, &1_000);
+
+    mint_pending(&e, &client, &farmer, "F1", "S1", 10 * TON);
+    e.ledger().set_timestamp(NOW + 400);
+    mint_pending(&e, &client, &farmer, "F1", "S2", 10 * TON);
+    e.ledger().set_timestamp(NOW + 900);
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S3", 10 * TON)),
+        Err(Ok(CarbonCreditError::RateLimited))
+    );
+    mint_pending(&e, &client, &neighbour, "F2", "S1", 10 * TON);
+
+    // The first mint leaves the window, freeing one slot
+    e.ledger().set_timestamp(NOW + 1_000);
+    mint_pending(&e, &client, &farmer, "F1", "S3", 10 * TON);
+    assert_eq!(
+        try_mint(&client, &mint_request(&e, &farmer, "F1", "S4", 10 * TON)),
+        Err(Ok(CarbonCreditError::RateLimited))
+    );
+
+    client.update_mint_rate_limit(&0, &0);
+    mint_pending(&e, &client, &farmer, "F1", "S4", 10 * TON);
+}